@@ -0,0 +1,370 @@
+use crate::url_components::decompose_url;
+use crate::InputValue;
+use regex::Regex;
+use std::io::{self, BufRead};
+
+// format of the Apache and nginx combined logs, also written by Squid with `logformat combined`
+pub const COMBINED_LOG_FORMAT: &str = r#"%h %l %u %t "%r" %>s %b "%{Referer}i" "%{User-agent}i""#;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Field {
+    Client,
+    Timestamp,
+    Request,
+    Url,
+    Host,
+    Referrer,
+    Status,
+}
+
+// access log format written with Apache directives such as `%h` and `%{Referer}i`, or nginx
+// variables such as `$remote_addr` and `$http_referer`
+#[derive(Clone, Debug)]
+pub struct LogFormat {
+    regex: Regex,
+    fields: Vec<Option<Field>>,
+}
+
+impl LogFormat {
+    pub fn parse(format: &str) -> Result<Self, String> {
+        let mut pattern: String = String::from("^");
+        let mut fields: Vec<Option<Field>> = Vec::new();
+        let mut literal: String = String::new();
+        let mut chars = format.chars().peekable();
+        while let Some(c) = chars.next() {
+            let field: Option<Field> = match c {
+                '%' if chars.peek() == Some(&'%') => {
+                    chars.next();
+                    literal.push('%');
+                    continue;
+                }
+                '%' => {
+                    while matches!(chars.peek(), Some('<' | '>')) {
+                        chars.next();
+                    }
+                    let mut argument: String = String::new();
+                    if chars.peek() == Some(&'{') {
+                        chars.next();
+                        for c in chars.by_ref() {
+                            if c == '}' {
+                                break;
+                            }
+                            argument.push(c);
+                        }
+                    }
+                    match chars.next() {
+                        Some(directive) => apache_field(directive, &argument),
+                        None => return Err(format!("Invalid log format {}", format)),
+                    }
+                }
+                '$' if chars.peek().is_some_and(|c| c.is_ascii_alphabetic()) => {
+                    let mut variable: String = String::new();
+                    while let Some(c) = chars
+                        .peek()
+                        .filter(|c| c.is_ascii_alphanumeric() || **c == '_')
+                    {
+                        variable.push(*c);
+                        chars.next();
+                    }
+                    nginx_field(&variable)
+                }
+                c => {
+                    literal.push(c);
+                    continue;
+                }
+            };
+            pattern.push_str(&regex::escape(&literal));
+            literal.clear();
+            pattern.push_str(if chars.peek().is_none() {
+                "(.*)"
+            } else {
+                "(.*?)"
+            });
+            fields.push(field);
+        }
+        pattern.push_str(&regex::escape(&literal));
+        if !fields.iter().any(Option::is_some) {
+            return Err(format!(
+                "The log format {} has no client, request, URL, host or referrer field",
+                format
+            ));
+        }
+        Ok(LogFormat {
+            regex: Regex::new(&pattern).map_err(|e| e.to_string())?,
+            fields,
+        })
+    }
+}
+
+fn apache_field(directive: char, argument: &str) -> Option<Field> {
+    match directive {
+        'h' | 'a' => Some(Field::Client),
+        't' => Some(Field::Timestamp),
+        'r' => Some(Field::Request),
+        'U' => Some(Field::Url),
+        'v' | 'V' => Some(Field::Host),
+        's' => Some(Field::Status),
+        'i' if argument.eq_ignore_ascii_case("host") => Some(Field::Host),
+        'i' if argument.eq_ignore_ascii_case("referer") => Some(Field::Referrer),
+        _ => None,
+    }
+}
+
+fn nginx_field(variable: &str) -> Option<Field> {
+    match variable {
+        "remote_addr" => Some(Field::Client),
+        "time_local" | "time_iso8601" | "msec" => Some(Field::Timestamp),
+        "request" => Some(Field::Request),
+        "request_uri" | "uri" => Some(Field::Url),
+        "host" | "http_host" | "server_name" => Some(Field::Host),
+        "http_referer" => Some(Field::Referrer),
+        "status" => Some(Field::Status),
+        _ => None,
+    }
+}
+
+// fields of a request, as found in an access log line
+#[derive(Default)]
+struct Request {
+    timestamp: Option<String>,
+    client: Option<String>,
+    status: Option<String>,
+    url: Option<String>,
+    host: Option<String>,
+    referrer: Option<String>,
+}
+
+impl Request {
+    fn emit<F: FnMut(InputValue)>(self, callback: &mut F) {
+        let mut context: Vec<(String, String)> = Vec::new();
+        for (key, value) in [
+            ("timestamp", &self.timestamp),
+            ("client", &self.client),
+            ("status", &self.status),
+        ] {
+            if let Some(value) = value {
+                context.push((key.to_string(), value.clone()));
+            }
+        }
+        // paths of requests to web servers are completed with the host
+        let url: Option<String> = match (&self.url, &self.host) {
+            (Some(url), Some(host)) if url.starts_with('/') => {
+                Some(format!("http://{}{}", host, url))
+            }
+            (Some(url), _) if url.contains("://") => Some(url.clone()),
+            _ => None,
+        };
+        let host: Option<String> = self.host.or_else(|| decompose_url(url.as_deref()?).host);
+        for (field, value) in [
+            ("client", self.client),
+            ("url", url),
+            ("host", host),
+            ("referrer", self.referrer),
+        ] {
+            let value: String = match value {
+                Some(value) if !value.is_empty() && value != "-" => value,
+                _ => continue,
+            };
+            let mut context: Vec<(String, String)> = context.clone();
+            context.push(("field".to_string(), field.to_string()));
+            callback(InputValue::with_context(value, context));
+        }
+    }
+}
+
+fn without_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') && port.chars().all(|c| c.is_ascii_digit()) => {
+            host
+        }
+        _ => host,
+    }
+}
+
+// reads the lines of an access log, returning an error when none of them has the format
+pub(crate) fn read_log_lines<F: FnMut(&str) -> bool>(
+    mut reader: impl BufRead,
+    mut read_line: F,
+) -> Result<(), io::Error> {
+    let mut line: String = String::new();
+    let mut nb_lines: usize = 0;
+    let mut nb_read_lines: usize = 0;
+    while reader.read_line(&mut line)? > 0 {
+        let trimmed: &str = line.trim();
+        if !trimmed.is_empty() && !trimmed.starts_with('#') {
+            nb_lines += 1;
+            if read_line(trimmed) {
+                nb_read_lines += 1;
+            }
+        }
+        line.clear();
+    }
+    if nb_lines > 0 && nb_read_lines == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "No line of the input has the format of the log",
+        ));
+    }
+    Ok(())
+}
+
+pub fn read_access_log<F: FnMut(InputValue)>(
+    reader: impl BufRead,
+    format: &LogFormat,
+    mut callback: F,
+) -> Result<(), io::Error> {
+    read_log_lines(reader, |line| {
+        let captures = match format.regex.captures(line) {
+            Some(captures) => captures,
+            None => return false,
+        };
+        let mut request = Request::default();
+        for (index, field) in format.fields.iter().enumerate() {
+            let value: String = match (field, captures.get(index + 1)) {
+                (Some(_), Some(value)) => value.as_str().trim().to_string(),
+                _ => continue,
+            };
+            match field {
+                Some(Field::Client) => request.client = Some(value),
+                Some(Field::Timestamp) => {
+                    request.timestamp = Some(value.trim_matches(['[', ']']).to_string())
+                }
+                // the URL of a request line such as `GET http://evil.com/ HTTP/1.1`
+                Some(Field::Request) => request.url = value.split(' ').nth(1).map(str::to_string),
+                Some(Field::Url) => request.url = Some(value),
+                Some(Field::Host) => request.host = Some(without_port(&value).to_string()),
+                Some(Field::Referrer) => request.referrer = Some(value),
+                Some(Field::Status) => request.status = Some(value),
+                None => (),
+            }
+        }
+        request.emit(&mut callback);
+        true
+    })
+}
+
+// reads the native Squid log format:
+// time elapsed client code/status bytes method URL user hierarchy/peer type
+pub fn read_squid_log<F: FnMut(InputValue)>(
+    reader: impl BufRead,
+    mut callback: F,
+) -> Result<(), io::Error> {
+    read_log_lines(reader, |line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 7 || fields[0].parse::<f64>().is_err() {
+            return false;
+        }
+        let mut request = Request {
+            timestamp: Some(fields[0].to_string()),
+            client: Some(fields[2].to_string()),
+            status: fields[3]
+                .split_once('/')
+                .map(|(_, status)| status.to_string()),
+            ..Default::default()
+        };
+        // CONNECT requests are logged with the host and port of the tunnel
+        if fields[5] == "CONNECT" {
+            request.host = Some(without_port(fields[6]).to_string());
+        } else {
+            request.url = Some(fields[6].to_string());
+        }
+        request.emit(&mut callback);
+        true
+    })
+}
+
+#[cfg(test)]
+fn read_values(
+    read: impl FnOnce(&mut dyn FnMut(InputValue)) -> Result<(), io::Error>,
+) -> Vec<(String, String)> {
+    let mut values: Vec<(String, String)> = Vec::new();
+    read(&mut |value| values.push((value.value.clone(), value.context_string()))).unwrap();
+    values
+}
+
+#[test]
+fn test_read_combined_log() {
+    let log: &str = "10.0.0.9 - - [02/May/2024:10:04:16 +0200] \"GET http://evil.com/a.php HTTP/1.1\" 200 512 \"http://bad.org/\" \"Mozilla/5.0\" TCP_MISS:HIER_DIRECT\n\
+        10.0.0.8 - frank [02/May/2024:10:04:17 +0200] \"POST /login HTTP/1.1\" 302 0 \"-\" \"curl/8.0\"\n";
+    let format = LogFormat::parse(COMBINED_LOG_FORMAT).unwrap();
+    let context = |status: &str, client: &str, field: &str| {
+        format!(
+            "timestamp=02/May/2024:10:04:1{} +0200;client={};status={};field={}",
+            if status == "200" { 6 } else { 7 },
+            client,
+            status,
+            field
+        )
+    };
+    assert_eq!(
+        read_values(|callback| read_access_log(log.as_bytes(), &format, callback)),
+        vec![
+            ("10.0.0.9".to_string(), context("200", "10.0.0.9", "client")),
+            (
+                "http://evil.com/a.php".to_string(),
+                context("200", "10.0.0.9", "url")
+            ),
+            ("evil.com".to_string(), context("200", "10.0.0.9", "host")),
+            (
+                "http://bad.org/".to_string(),
+                context("200", "10.0.0.9", "referrer")
+            ),
+            ("10.0.0.8".to_string(), context("302", "10.0.0.8", "client")),
+        ]
+    );
+    assert!(read_access_log("not a log line\n".as_bytes(), &format, |_| ()).is_err());
+}
+
+#[test]
+fn test_read_custom_log() {
+    let format =
+        LogFormat::parse("$remote_addr [$time_local] $host \"$request\" $status \"$http_referer\"")
+            .unwrap();
+    let log: &str = "10.0.0.9 [02/May/2024:10:04:16 +0200] www.evil.com:8080 \"GET /a.php?b=c HTTP/1.1\" 404 \"-\"\n";
+    let context = |field: &str| {
+        format!(
+            "timestamp=02/May/2024:10:04:16 +0200;client=10.0.0.9;status=404;field={}",
+            field
+        )
+    };
+    assert_eq!(
+        read_values(|callback| read_access_log(log.as_bytes(), &format, callback)),
+        vec![
+            ("10.0.0.9".to_string(), context("client")),
+            ("http://www.evil.com/a.php?b=c".to_string(), context("url")),
+            ("www.evil.com".to_string(), context("host")),
+        ]
+    );
+    assert!(LogFormat::parse("%b %D").is_err());
+}
+
+#[test]
+fn test_read_squid_log() {
+    let log: &str = "1286536309.450    120 10.0.0.9 TCP_MISS/200 507 GET http://evil.com/a.php - HIER_DIRECT/1.2.3.4 text/html\n\
+        1286536310.001      5 10.0.0.8 TCP_TUNNEL/200 3000 CONNECT bad.org:443 - HIER_DIRECT/5.6.7.8 -\n";
+    assert_eq!(
+        read_values(|callback| read_squid_log(log.as_bytes(), callback)),
+        vec![
+            (
+                "10.0.0.9".to_string(),
+                "timestamp=1286536309.450;client=10.0.0.9;status=200;field=client".to_string()
+            ),
+            (
+                "http://evil.com/a.php".to_string(),
+                "timestamp=1286536309.450;client=10.0.0.9;status=200;field=url".to_string()
+            ),
+            (
+                "evil.com".to_string(),
+                "timestamp=1286536309.450;client=10.0.0.9;status=200;field=host".to_string()
+            ),
+            (
+                "10.0.0.8".to_string(),
+                "timestamp=1286536310.001;client=10.0.0.8;status=200;field=client".to_string()
+            ),
+            (
+                "bad.org".to_string(),
+                "timestamp=1286536310.001;client=10.0.0.8;status=200;field=host".to_string()
+            ),
+        ]
+    );
+}
@@ -0,0 +1,159 @@
+use crate::canonical::canonicalize;
+use crate::ip_ranges::parse_ip_range;
+use crate::public_suffix::registered_domain;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum AtomType {
+    Ipv4,
+    Ipv6,
+    IpRange,
+    Domain,
+    Fqdn,
+    Url,
+    Email,
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+    Unknown,
+}
+
+impl AtomType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AtomType::Ipv4 => "ipv4",
+            AtomType::Ipv6 => "ipv6",
+            AtomType::IpRange => "ip_range",
+            AtomType::Domain => "domain",
+            AtomType::Fqdn => "fqdn",
+            AtomType::Url => "url",
+            AtomType::Email => "email",
+            AtomType::Md5 => "md5",
+            AtomType::Sha1 => "sha1",
+            AtomType::Sha256 => "sha256",
+            AtomType::Sha512 => "sha512",
+            AtomType::Unknown => "unknown",
+        }
+    }
+
+    // the atom type Datalake expects for values of this type, hashes can be of several atom types
+    pub fn datalake_atom_type<'a>(&self, treat_hashes_like: &'a str) -> Option<&'a str> {
+        match self {
+            AtomType::Ipv4 | AtomType::Ipv6 => Some("ip"),
+            AtomType::Domain => Some("domain"),
+            AtomType::Fqdn => Some("fqdn"),
+            AtomType::Url => Some("url"),
+            AtomType::Email => Some("email"),
+            AtomType::Md5 | AtomType::Sha1 | AtomType::Sha256 | AtomType::Sha512 => {
+                Some(treat_hashes_like)
+            }
+            AtomType::IpRange | AtomType::Unknown => None,
+        }
+    }
+}
+
+impl fmt::Display for AtomType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+pub fn detect_atom_type(value: &str) -> AtomType {
+    let value: String = canonicalize(value);
+    match value.parse::<IpAddr>() {
+        Ok(IpAddr::V4(_)) => return AtomType::Ipv4,
+        Ok(IpAddr::V6(_)) => return AtomType::Ipv6,
+        Err(_) => (),
+    }
+    if parse_ip_range(&value).is_some() {
+        return AtomType::IpRange;
+    }
+    if value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return match value.len() {
+            32 => AtomType::Md5,
+            40 => AtomType::Sha1,
+            64 => AtomType::Sha256,
+            128 => AtomType::Sha512,
+            _ => AtomType::Unknown,
+        };
+    }
+    if value.contains(char::is_whitespace) {
+        return AtomType::Unknown;
+    }
+    if let Some((scheme, rest)) = value.split_once("://") {
+        let host_is_empty: bool = rest.starts_with(['/', '?', '#']) || rest.is_empty();
+        if !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphabetic()) && !host_is_empty {
+            return AtomType::Url;
+        }
+        return AtomType::Unknown;
+    }
+    if let Some((local_part, domain)) = value.rsplit_once('@') {
+        if !local_part.is_empty() && is_domain_name(domain) {
+            return AtomType::Email;
+        }
+        return AtomType::Unknown;
+    }
+    if !is_domain_name(&value) {
+        return AtomType::Unknown;
+    }
+    // domains are registered under a public suffix, FQDNs are their subdomains
+    match registered_domain(&value) {
+        Some(registered) if registered != value => AtomType::Fqdn,
+        _ => AtomType::Domain,
+    }
+}
+
+// domain names with at least two labels
+fn is_domain_name(domain: &str) -> bool {
+    let labels: Vec<&str> = domain.split('.').collect();
+    if labels.len() < 2 || domain.len() > 253 {
+        return false;
+    }
+    let are_labels_valid: bool = labels.iter().all(|label| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    });
+    let tld: &str = labels[labels.len() - 1];
+    let is_tld_valid: bool =
+        tld.starts_with("xn--") || tld.chars().all(|c| c.is_ascii_alphabetic());
+    are_labels_valid && is_tld_valid
+}
+
+#[test]
+fn test_detect_atom_type() {
+    assert_eq!(detect_atom_type("10.0.0.1"), AtomType::Ipv4);
+    assert_eq!(detect_atom_type("010.000.000.001"), AtomType::Ipv4);
+    assert_eq!(detect_atom_type("[2001:db8::1]"), AtomType::Ipv6);
+    assert_eq!(detect_atom_type("10.0.0.0/8"), AtomType::IpRange);
+    assert_eq!(detect_atom_type("10.0.0.1-10.0.0.9"), AtomType::IpRange);
+    assert_eq!(detect_atom_type("Evil.com."), AtomType::Domain);
+    assert_eq!(detect_atom_type("cdn.login.evil.com"), AtomType::Fqdn);
+    assert_eq!(detect_atom_type("evil.co.uk"), AtomType::Domain);
+    assert_eq!(detect_atom_type("www.evil.co.uk"), AtomType::Fqdn);
+    assert_eq!(detect_atom_type("bücher.example"), AtomType::Domain);
+    assert_eq!(detect_atom_type("http://evil.com/a.php"), AtomType::Url);
+    assert_eq!(detect_atom_type("bad@evil.com"), AtomType::Email);
+    assert_eq!(
+        detect_atom_type("D41D8CD98F00B204E9800998ECF8427E"),
+        AtomType::Md5
+    );
+    assert_eq!(
+        detect_atom_type("da39a3ee5e6b4b0d3255bfef95601890afd80709"),
+        AtomType::Sha1
+    );
+    assert_eq!(detect_atom_type(&"a".repeat(64)), AtomType::Sha256);
+    assert_eq!(detect_atom_type(&"a".repeat(128)), AtomType::Sha512);
+    assert_eq!(detect_atom_type("test1"), AtomType::Unknown);
+    assert_eq!(detect_atom_type("invoice.exe 2"), AtomType::Unknown);
+    assert_eq!(detect_atom_type("1.2.3.4.5"), AtomType::Unknown);
+    assert_eq!(detect_atom_type("http:///path"), AtomType::Unknown);
+}
@@ -0,0 +1,279 @@
+use crate::{deserialize_bloom_file, get_filename_from_path, now_timestamp, to_hex, BloomMetadata};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_FILENAME: &str = "manifest.ron";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BundleEntry {
+    pub name: String,
+    pub checksum: String,
+    pub metadata: BloomMetadata,
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BundleManifest {
+    pub created_at: u64,
+    pub filters: Vec<BundleEntry>,
+}
+
+pub fn read_signing_key(path: &PathBuf) -> Result<Vec<u8>, String> {
+    match std::fs::read(path) {
+        Ok(key) if key.is_empty() => Err(format!("{}: The key is empty", path.display())),
+        Ok(key) => Ok(key),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+fn entry_mac(key: &[u8], name: &str, checksum: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(name.as_bytes());
+    mac.update(b":");
+    mac.update(checksum.as_bytes());
+    mac
+}
+
+fn sign_entry(key: &[u8], name: &str, checksum: &str) -> String {
+    to_hex(&entry_mac(key, name, checksum).finalize().into_bytes())
+}
+
+// the signature is compared in constant time so it can't be guessed byte by byte
+fn verify_entry(key: &[u8], name: &str, checksum: &str, signature: &str) -> bool {
+    match from_hex(signature) {
+        Some(signature) => entry_mac(key, name, checksum)
+            .verify_slice(&signature)
+            .is_ok(),
+        None => false,
+    }
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    let digits = hex.as_bytes().chunks_exact(2);
+    if !digits.remainder().is_empty() {
+        return None;
+    }
+    digits
+        .map(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok())
+        .collect()
+}
+
+fn is_valid_filter_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != MANIFEST_FILENAME
+        && name != "."
+        && name != ".."
+        && !name.contains(['/', '\\'])
+}
+
+pub fn export_bundle(
+    bloom_paths: &[PathBuf],
+    output_path: &PathBuf,
+    key: Option<&[u8]>,
+) -> Result<BundleManifest, String> {
+    let mut manifest = BundleManifest {
+        created_at: now_timestamp(),
+        filters: Vec::new(),
+    };
+    let mut contents: Vec<(String, Vec<u8>)> = Vec::new();
+    for path in bloom_paths {
+        let name: String = get_filename_from_path(path)?;
+        if contents.iter().any(|(existing, _)| existing == &name) {
+            return Err(format!(
+                "{}: Another bloom filter named {} is already in the bundle",
+                path.display(),
+                name
+            ));
+        }
+        // deserializing makes sure only valid bloom filters are exported
+        let metadata: BloomMetadata = deserialize_bloom_file(path)?.metadata;
+        let content: Vec<u8> = match std::fs::read(path) {
+            Ok(content) => content,
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        let checksum: String = to_hex(&Sha256::digest(&content));
+        let signature: Option<String> = key.map(|key| sign_entry(key, &name, &checksum));
+        manifest.filters.push(BundleEntry {
+            name: name.clone(),
+            checksum,
+            metadata,
+            signature,
+        });
+        contents.push((name, content));
+    }
+
+    let serialized_manifest: String =
+        ron::to_string(&manifest).expect("Failed to serialize the bundle manifest");
+    let output_file: File = match File::create(output_path) {
+        Ok(output_file) => output_file,
+        Err(e) => return Err(format!("{}: {}", output_path.display(), e)),
+    };
+    let mut archive = tar::Builder::new(GzEncoder::new(output_file, Compression::default()));
+    let mut write_entry = |name: &str, content: &[u8]| {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(manifest.created_at);
+        header.set_cksum();
+        archive.append_data(&mut header, name, content)
+    };
+    let mut result = write_entry(MANIFEST_FILENAME, serialized_manifest.as_bytes());
+    for (name, content) in &contents {
+        result = result.and_then(|()| write_entry(name, content));
+    }
+    match result
+        .and_then(|()| archive.into_inner())
+        .and_then(|encoder| encoder.finish())
+    {
+        Ok(_) => Ok(manifest),
+        Err(e) => Err(format!("{}: {}", output_path.display(), e)),
+    }
+}
+
+pub fn read_bundle(
+    bundle_path: &PathBuf,
+    key: Option<&[u8]>,
+) -> Result<(BundleManifest, HashMap<String, Vec<u8>>), String> {
+    let bundle_file: File = match File::open(bundle_path) {
+        Ok(bundle_file) => bundle_file,
+        Err(e) => return Err(format!("{}: {}", bundle_path.display(), e)),
+    };
+    let mut archive = tar::Archive::new(GzDecoder::new(bundle_file));
+    let mut files: HashMap<String, Vec<u8>> = HashMap::new();
+    let entries = match archive.entries() {
+        Ok(entries) => entries,
+        Err(e) => return Err(format!("{}: {}", bundle_path.display(), e)),
+    };
+    for entry in entries {
+        let mut content: Vec<u8> = Vec::new();
+        let name: String = match entry.and_then(|mut entry| {
+            entry.read_to_end(&mut content)?;
+            Ok(entry.path()?.to_string_lossy().to_string())
+        }) {
+            Ok(name) => name,
+            Err(e) => return Err(format!("{}: {}", bundle_path.display(), e)),
+        };
+        if files.insert(name.clone(), content).is_some() {
+            return Err(format!("{} is present twice in the bundle", name));
+        }
+    }
+
+    let manifest: BundleManifest = match files.remove(MANIFEST_FILENAME) {
+        Some(content) => match ron::from_str(&String::from_utf8_lossy(&content)) {
+            Ok(manifest) => manifest,
+            Err(e) => return Err(format!("Failed to read {}: {}", MANIFEST_FILENAME, e)),
+        },
+        None => return Err(format!("No {} found in the bundle", MANIFEST_FILENAME)),
+    };
+    for entry in &manifest.filters {
+        if !is_valid_filter_name(&entry.name) {
+            return Err(format!("{} is not a valid bloom filter name", entry.name));
+        }
+        let content: &Vec<u8> = match files.get(&entry.name) {
+            Some(content) => content,
+            None => {
+                return Err(format!(
+                    "{} is listed in the manifest but missing",
+                    entry.name
+                ))
+            }
+        };
+        if to_hex(&Sha256::digest(content)) != entry.checksum {
+            return Err(format!("{} does not match its checksum", entry.name));
+        }
+        if let Some(key) = key {
+            match &entry.signature {
+                Some(signature) if verify_entry(key, &entry.name, &entry.checksum, signature) => {}
+                Some(_) => return Err(format!("{} has an invalid signature", entry.name)),
+                None => return Err(format!("{} is not signed", entry.name)),
+            }
+        }
+    }
+    if let Some(name) = files
+        .keys()
+        .find(|name| !manifest.filters.iter().any(|entry| &entry.name == *name))
+    {
+        return Err(format!("{} is not listed in the manifest", name));
+    }
+    Ok((manifest, files))
+}
+
+pub fn import_bundle(
+    bundle_path: &PathBuf,
+    output_dir: &Path,
+    key: Option<&[u8]>,
+    force: bool,
+) -> Result<BundleManifest, String> {
+    let (manifest, files) = read_bundle(bundle_path, key)?;
+    if !force {
+        for entry in &manifest.filters {
+            let path: PathBuf = output_dir.join(&entry.name);
+            if path.exists() {
+                return Err(format!(
+                    "{}: The bloom filter already exists, use `--force` to replace it",
+                    path.display()
+                ));
+            }
+        }
+    }
+    if let Err(e) = std::fs::create_dir_all(output_dir) {
+        return Err(format!("{}: {}", output_dir.display(), e));
+    }
+    // the bloom filters are written to temporary files first, which are read back so that a
+    // corrupted filter is never installed, and only renamed once they are all valid so that a
+    // failure doesn't leave only part of the bundle installed
+    let mut written: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for entry in &manifest.filters {
+        let path: PathBuf = output_dir.join(&entry.name);
+        let temporary_path: PathBuf = output_dir.join(format!(".{}.part", entry.name));
+        let result: Result<(), String> = std::fs::write(&temporary_path, &files[&entry.name])
+            .map_err(|e| format!("{}: {}", path.display(), e))
+            .and_then(|()| {
+                deserialize_bloom_file(&temporary_path).map_err(|_| {
+                    format!(
+                        "{}: The bloom filter of the bundle can't be deserialized",
+                        entry.name
+                    )
+                })
+            })
+            .map(|_| ());
+        if let Err(e) = result {
+            let _ = std::fs::remove_file(&temporary_path);
+            for (temporary_path, _) in &written {
+                let _ = std::fs::remove_file(temporary_path);
+            }
+            return Err(e);
+        }
+        written.push((temporary_path, path));
+    }
+    for (temporary_path, path) in &written {
+        if let Err(e) = std::fs::rename(temporary_path, path) {
+            return Err(format!("{}: {}", path.display(), e));
+        }
+    }
+    Ok(manifest)
+}
+
+#[test]
+fn test_verify_entry() {
+    let signature: String = sign_entry(b"secret", "c2.bloom", "abc");
+    assert!(verify_entry(b"secret", "c2.bloom", "abc", &signature));
+    assert!(!verify_entry(b"wrong", "c2.bloom", "abc", &signature));
+    assert!(!verify_entry(
+        b"secret",
+        "phishing.bloom",
+        "abc",
+        &signature
+    ));
+    assert!(!verify_entry(b"secret", "c2.bloom", "abc", &signature[1..]));
+    assert!(!verify_entry(b"secret", "c2.bloom", "abc", "zz"));
+}
@@ -0,0 +1,126 @@
+use std::net::{IpAddr, Ipv6Addr};
+
+// to be increased whenever a change below makes a value canonicalize differently,
+// bloom filters record it so those created with another version can be detected
+pub const CANONICALIZATION_VERSION: u32 = 1;
+
+pub fn canonicalize(value: &str) -> String {
+    let value: &str = value.trim();
+    if let Some(ip) = canonicalize_ip(value) {
+        return ip;
+    }
+    if is_hash(value) {
+        return value.to_lowercase();
+    }
+    if let Some(scheme_end) = value.find("://") {
+        return canonicalize_url(value, scheme_end);
+    }
+    if value.contains(char::is_whitespace) || value.contains('/') {
+        return value.to_string();
+    }
+    if let Some((local_part, domain)) = value.rsplit_once('@') {
+        return format!("{}@{}", local_part, canonicalize_domain(domain));
+    }
+    if value.contains('.') {
+        return canonicalize_domain(value);
+    }
+    value.to_string()
+}
+
+fn is_hash(value: &str) -> bool {
+    matches!(value.len(), 32 | 40 | 64 | 128) && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn canonicalize_ip(value: &str) -> Option<String> {
+    let unbracketed: &str = value
+        .strip_prefix('[')
+        .and_then(|value| value.strip_suffix(']'))
+        .unwrap_or(value);
+    if let Ok(ip) = unbracketed.parse::<IpAddr>() {
+        return Some(ip.to_string());
+    }
+    // IPv4 addresses with leading zeros such as 010.001.002.003
+    let octets: Vec<&str> = value.split('.').collect();
+    if octets.len() == 4
+        && octets.iter().all(|octet| {
+            !octet.is_empty() && octet.len() <= 3 && octet.chars().all(|c| c.is_ascii_digit())
+        })
+    {
+        let octets: Vec<u16> = octets
+            .iter()
+            .map(|octet| octet.parse().unwrap_or(256))
+            .collect();
+        if octets.iter().all(|octet| *octet <= 255) {
+            return Some(format!(
+                "{}.{}.{}.{}",
+                octets[0], octets[1], octets[2], octets[3]
+            ));
+        }
+    }
+    None
+}
+
+pub fn canonicalize_domain(domain: &str) -> String {
+    let domain: &str = domain.trim_end_matches('.');
+    match idna::domain_to_ascii(domain) {
+        Ok(ascii) if !ascii.is_empty() => ascii,
+        _ => domain.to_lowercase(),
+    }
+}
+
+fn canonicalize_url(url: &str, scheme_end: usize) -> String {
+    let scheme: String = url[..scheme_end].to_lowercase();
+    let rest: &str = &url[scheme_end + 3..];
+    let authority_end: usize = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, path) = rest.split_at(authority_end);
+    let (userinfo, host_port) = match authority.rsplit_once('@') {
+        Some((userinfo, host_port)) => (format!("{}@", userinfo), host_port),
+        None => (String::new(), authority),
+    };
+    let host: String = if host_port.starts_with('[') {
+        // IPv6 literal, with an optional port after the closing bracket
+        match host_port.find(']') {
+            Some(end) => match host_port[1..end].parse::<Ipv6Addr>() {
+                Ok(ip) => format!("[{}]{}", ip, &host_port[end + 1..]),
+                Err(_) => host_port.to_lowercase(),
+            },
+            None => host_port.to_lowercase(),
+        }
+    } else {
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => {
+                (host, format!(":{}", port))
+            }
+            _ => (host_port, String::new()),
+        };
+        let host: String = canonicalize_ip(host).unwrap_or_else(|| canonicalize_domain(host));
+        format!("{}{}", host, port)
+    };
+    format!("{}://{}{}{}", scheme, userinfo, host, path)
+}
+
+#[test]
+fn test_canonicalize() {
+    assert_eq!(canonicalize(" FOO.com. "), "foo.com");
+    assert_eq!(
+        canonicalize("D41D8CD98F00B204E9800998ECF8427E"),
+        "d41d8cd98f00b204e9800998ecf8427e"
+    );
+    assert_eq!(
+        canonicalize("2001:0DB8:0000:0000:0000:0000:0000:0001"),
+        "2001:db8::1"
+    );
+    assert_eq!(canonicalize("[2001:db8::1]"), "2001:db8::1");
+    assert_eq!(canonicalize("010.001.002.003"), "10.1.2.3");
+    assert_eq!(canonicalize("bücher.example"), "xn--bcher-kva.example");
+    assert_eq!(canonicalize("Admin@EXAMPLE.com"), "Admin@example.com");
+    assert_eq!(
+        canonicalize("HTTPS://User@WWW.Evil.COM.:8443/Path/Index.PHP?Q=A"),
+        "https://User@www.evil.com:8443/Path/Index.PHP?Q=A"
+    );
+    assert_eq!(
+        canonicalize("http://[2001:DB8::0001]:80/"),
+        "http://[2001:db8::1]:80/"
+    );
+    assert_eq!(canonicalize("test1"), "test1");
+}
@@ -0,0 +1,72 @@
+use bzip2::bufread::MultiBzDecoder;
+use flate2::bufread::MultiGzDecoder;
+use std::io::{self, BufRead, BufReader};
+use xz2::bufread::XzDecoder;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+const MAGIC_BYTES: [(Compression, &[u8]); 4] = [
+    (Compression::Gzip, &[0x1f, 0x8b]),
+    (Compression::Zstd, &[0x28, 0xb5, 0x2f, 0xfd]),
+    (Compression::Bzip2, b"BZh"),
+    (Compression::Xz, &[0xfd, b'7', b'z', b'X', b'Z', 0x00]),
+];
+
+pub fn detect_compression(header: &[u8]) -> Option<Compression> {
+    MAGIC_BYTES
+        .iter()
+        .find(|(_, magic)| header.starts_with(magic))
+        .map(|(compression, _)| *compression)
+}
+
+// the magic bytes are peeked from the buffer of the reader, so uncompressed input is read as is
+pub fn decompress_reader<'a>(
+    mut reader: Box<dyn BufRead + 'a>,
+) -> io::Result<Box<dyn BufRead + 'a>> {
+    let compression: Option<Compression> = detect_compression(reader.fill_buf()?);
+    Ok(match compression {
+        Some(Compression::Gzip) => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Some(Compression::Zstd) => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        Some(Compression::Bzip2) => Box::new(BufReader::new(MultiBzDecoder::new(reader))),
+        Some(Compression::Xz) => Box::new(BufReader::new(XzDecoder::new_multi_decoder(reader))),
+        None => reader,
+    })
+}
+
+#[test]
+fn test_decompress_reader() {
+    use std::io::{Read, Write};
+
+    let content: &[u8] = b"evil.com\n10.0.0.1\n";
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(content).unwrap();
+    let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+    bzip2.write_all(content).unwrap();
+    let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+    xz.write_all(content).unwrap();
+    let compressed: Vec<(Option<Compression>, Vec<u8>)> = vec![
+        (Some(Compression::Gzip), gzip.finish().unwrap()),
+        (
+            Some(Compression::Zstd),
+            zstd::encode_all(content, 0).unwrap(),
+        ),
+        (Some(Compression::Bzip2), bzip2.finish().unwrap()),
+        (Some(Compression::Xz), xz.finish().unwrap()),
+        (None, content.to_vec()),
+    ];
+    for (compression, data) in compressed {
+        assert_eq!(detect_compression(&data), compression);
+        let mut decompressed: Vec<u8> = Vec::new();
+        decompress_reader(Box::new(data.as_slice()))
+            .unwrap()
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, content);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::atom_type::{detect_atom_type, AtomType};
+use crate::canonical::canonicalize;
+use crate::public_suffix::has_known_suffix;
+use regex::Regex;
+use std::sync::OnceLock;
+
+struct RefangPatterns {
+    scheme: Regex,
+    dot: Regex,
+    at: Regex,
+    words: Regex,
+    word: Regex,
+    colon_slashes: Regex,
+    colon: Regex,
+    slash: Regex,
+}
+
+fn refang_patterns() -> &'static RefangPatterns {
+    static PATTERNS: OnceLock<RefangPatterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| RefangPatterns {
+        scheme: Regex::new(r"(?i)\b(?:h(?:xx|tx|xt)p|(h)ttp|fxp|(f)tp)(s?)(\[:\]|\[://\]|:)")
+            .unwrap(),
+        dot: Regex::new(r"\s?(?:\[\.\]|\(\.\)|\{\.\})\s?").unwrap(),
+        at: Regex::new(r"\s?(?:\[@\]|\(@\)|\{@\})\s?").unwrap(),
+        // the words are only replaced in what then reads as an email address or a domain
+        words: Regex::new(
+            r"(?i)[a-z0-9._%+-]+(?:\s?(?:\[(?:at|dot)\]|\((?:at|dot)\)|\{(?:at|dot)\})\s?[a-z0-9._%+-]+)+",
+        )
+        .unwrap(),
+        word: Regex::new(r"(?i)\s?(?:\[(at|dot)\]|\((at|dot)\)|\{(at|dot)\})\s?").unwrap(),
+        colon_slashes: Regex::new(r"\[://\]").unwrap(),
+        colon: Regex::new(r"\[:\]").unwrap(),
+        slash: Regex::new(r"\[/\]").unwrap(),
+    })
+}
+
+pub fn refang(value: &str) -> String {
+    let patterns = refang_patterns();
+    let value = patterns
+        .scheme
+        .replace_all(value, |caps: &regex::Captures| {
+            let scheme: &str = if caps.get(2).is_some() || caps[0].to_lowercase().starts_with("fxp")
+            {
+                "ftp"
+            } else {
+                "http"
+            };
+            let separator: &str = if &caps[4] == "[://]" { "://" } else { ":" };
+            format!("{}{}{}", scheme, &caps[3], separator)
+        });
+    let value = patterns.dot.replace_all(&value, ".");
+    let value = patterns.at.replace_all(&value, "@");
+    let value = patterns
+        .words
+        .replace_all(&value, |caps: &regex::Captures| {
+            let refanged: String = patterns
+                .word
+                .replace_all(&caps[0], |word: &regex::Captures| {
+                    let word = word.get(1).or(word.get(2)).or(word.get(3));
+                    match word.map(|word| word.as_str().to_lowercase()).as_deref() {
+                        Some("at") => "@",
+                        _ => ".",
+                    }
+                })
+                .to_string();
+            if is_email_or_domain(&refanged) {
+                refanged
+            } else {
+                caps[0].to_string()
+            }
+        });
+    let value = patterns.colon_slashes.replace_all(&value, "://");
+    let value = patterns.colon.replace_all(&value, ":");
+    patterns.slash.replace_all(&value, "/").to_string()
+}
+
+fn is_email_or_domain(value: &str) -> bool {
+    let value: String = canonicalize(value);
+    let domain: &str = match detect_atom_type(&value) {
+        AtomType::Email => value.rsplit_once('@').map_or("", |(_, domain)| domain),
+        AtomType::Domain | AtomType::Fqdn => &value,
+        _ => return false,
+    };
+    has_known_suffix(domain)
+}
+
+pub fn defang(value: &str) -> String {
+    let lowercase: String = value.to_lowercase();
+    let mut defanged: String =
+        if lowercase.starts_with("http://") || lowercase.starts_with("https://") {
+            format!("hxxp{}", &value[4..])
+        } else if lowercase.starts_with("ftp://") || lowercase.starts_with("ftps://") {
+            format!("fxp{}", &value[3..])
+        } else {
+            value.to_string()
+        };
+    defanged = defanged.replace('.', "[.]");
+    defanged.replace('@', "[@]")
+}
+
+#[test]
+fn test_refang() {
+    assert_eq!(refang("hxxp://evil[.]com"), "http://evil.com");
+    assert_eq!(
+        refang("hXXps[://]evil(.)com/a[.]php"),
+        "https://evil.com/a.php"
+    );
+    assert_eq!(refang("1.2.3[.]4"), "1.2.3.4");
+    assert_eq!(refang("bad[at]evil[dot]com"), "bad@evil.com");
+    assert_eq!(refang("evil [.] com"), "evil.com");
+    assert_eq!(refang("fxp://files{.}evil.com"), "ftp://files.evil.com");
+    assert_eq!(refang("http://clean.com"), "http://clean.com");
+    assert_eq!(
+        refang("contact bad [at] evil (dot) com now"),
+        "contact bad@evil.com now"
+    );
+    // the words are kept when they don't make an email address or a domain
+    assert_eq!(refang("report(at)home.pdf"), "report(at)home.pdf");
+    assert_eq!(refang("minutes [dot] docx"), "minutes [dot] docx");
+    assert_eq!(
+        refang("Mozilla/5.0 (at) work (dot) v2"),
+        "Mozilla/5.0 (at) work (dot) v2"
+    );
+}
+
+#[test]
+fn test_defang() {
+    assert_eq!(
+        defang("https://evil.com/a.php"),
+        "hxxps://evil[.]com/a[.]php"
+    );
+    assert_eq!(defang("bad@evil.com"), "bad[@]evil[.]com");
+    assert_eq!(defang("1.2.3.4"), "1[.]2[.]3[.]4");
+    assert_eq!(defang("httpbin.org"), "httpbin[.]org");
+    assert_eq!(refang(&defang("ftp://evil.com")), "ftp://evil.com");
+}
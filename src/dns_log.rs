@@ -0,0 +1,267 @@
+use crate::access_log::read_log_lines;
+use crate::InputValue;
+use std::io::{self, BufRead};
+use std::net::IpAddr;
+
+// query read from a line of a DNS resolver log
+struct DnsQuery {
+    timestamp: Option<String>,
+    client: String,
+    name: String,
+}
+
+impl DnsQuery {
+    fn emit<F: FnMut(InputValue)>(self, callback: &mut F) {
+        let mut context: Vec<(String, String)> = Vec::new();
+        if let Some(timestamp) = self.timestamp {
+            context.push(("timestamp".to_string(), timestamp));
+        }
+        context.push(("client".to_string(), self.client.clone()));
+        for (field, value) in [("client", self.client), ("query", self.name)] {
+            let mut context: Vec<(String, String)> = context.clone();
+            context.push(("field".to_string(), field.to_string()));
+            callback(InputValue::with_context(value, context));
+        }
+    }
+}
+
+fn read_dns_log<F: FnMut(InputValue)>(
+    reader: impl BufRead,
+    parse_line: fn(&str) -> Option<DnsQuery>,
+    mut callback: F,
+) -> Result<(), io::Error> {
+    read_log_lines(reader, |line| match parse_line(line) {
+        Some(query) => {
+            query.emit(&mut callback);
+            true
+        }
+        None => false,
+    })
+}
+
+// the queried name without the trailing dot of fully qualified names
+fn query_name(name: &str) -> Option<String> {
+    let name: &str = name.trim_end_matches('.');
+    if name.is_empty() {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+// the timestamp of syslog lines, such as `May  2 10:04:16` or `2024-05-02T10:04:16+02:00`
+fn syslog_timestamp(line: &str) -> Option<String> {
+    let mut tokens = line.split_whitespace();
+    let first: &str = tokens.next()?;
+    if first.starts_with(|c: char| c.is_ascii_digit()) {
+        return Some(first.to_string());
+    }
+    Some(format!("{} {} {}", first, tokens.next()?, tokens.next()?))
+}
+
+// 02-May-2024 10:04:16.123 queries: info: client @0x7f2b1c0 10.0.0.9#52345 (www.evil.com): query: www.evil.com IN A +E(0)K (10.0.0.1)
+fn parse_bind_line(line: &str) -> Option<DnsQuery> {
+    let (before, after) = line.split_once("client ")?;
+    let after: &str = after.strip_prefix('@').map_or(after, |after| {
+        after.split_once(' ').map_or("", |(_, after)| after)
+    });
+    let (client, after) = after.split_once(' ')?;
+    let client: &str = client.trim_end_matches(':');
+    let client: &str = client.rsplit_once('#').map_or(client, |(client, _)| client);
+    client.parse::<IpAddr>().ok()?;
+    let (_, query) = after.split_once("query: ")?;
+    let name: String = query_name(query.split_whitespace().next()?)?;
+    let timestamp: Vec<&str> = before.split_whitespace().take(2).collect();
+    Some(DnsQuery {
+        timestamp: match timestamp.first() {
+            Some(date) if date.starts_with(|c: char| c.is_ascii_digit()) => {
+                Some(timestamp.join(" "))
+            }
+            _ => None,
+        },
+        client: client.to_string(),
+        name,
+    })
+}
+
+pub fn read_bind_log<F: FnMut(InputValue)>(
+    reader: impl BufRead,
+    callback: F,
+) -> Result<(), io::Error> {
+    read_dns_log(reader, parse_bind_line, callback)
+}
+
+// May  2 10:04:16 dnsmasq[1234]: query[A] www.evil.com from 10.0.0.9
+fn parse_dnsmasq_line(line: &str) -> Option<DnsQuery> {
+    let (_, query) = line.split_once(" query[")?;
+    let (_, query) = query.split_once("] ")?;
+    let (name, client) = query.split_once(" from ")?;
+    let client: &str = client.trim();
+    client.parse::<IpAddr>().ok()?;
+    Some(DnsQuery {
+        timestamp: syslog_timestamp(line),
+        client: client.to_string(),
+        name: query_name(name.trim())?,
+    })
+}
+
+pub fn read_dnsmasq_log<F: FnMut(InputValue)>(
+    reader: impl BufRead,
+    callback: F,
+) -> Result<(), io::Error> {
+    read_dns_log(reader, parse_dnsmasq_line, callback)
+}
+
+// [1714644256] unbound[1234:0] info: 10.0.0.9 www.evil.com. A IN
+fn parse_unbound_line(line: &str) -> Option<DnsQuery> {
+    let (_, query) = line.split_once(" info: ")?;
+    let fields: Vec<&str> = query.split_whitespace().collect();
+    if fields.len() < 4 {
+        return None;
+    }
+    fields[0].parse::<IpAddr>().ok()?;
+    let timestamp: Option<String> = match line.strip_prefix('[') {
+        Some(line) => line
+            .split_once(']')
+            .map(|(timestamp, _)| timestamp.to_string()),
+        None => syslog_timestamp(line),
+    };
+    Some(DnsQuery {
+        timestamp,
+        client: fields[0].to_string(),
+        name: query_name(fields[1])?,
+    })
+}
+
+pub fn read_unbound_log<F: FnMut(InputValue)>(
+    reader: impl BufRead,
+    callback: F,
+) -> Result<(), io::Error> {
+    read_dns_log(reader, parse_unbound_line, callback)
+}
+
+// decodes the names of Windows DNS debug logs, written as (3)www(4)evil(3)com(0)
+fn decode_labels(name: &str) -> Option<String> {
+    let mut labels: Vec<&str> = Vec::new();
+    let mut rest: &str = name;
+    while let Some(label) = rest.strip_prefix('(') {
+        let (length, label) = label.split_once(')')?;
+        length.parse::<usize>().ok()?;
+        let end: usize = label.find('(').unwrap_or(label.len());
+        if end > 0 {
+            labels.push(&label[..end]);
+        }
+        rest = &label[end..];
+    }
+    if !rest.is_empty() || labels.is_empty() {
+        return None;
+    }
+    Some(labels.join("."))
+}
+
+// 5/2/2024 10:04:16 AM 0A3C PACKET  000000B1E1A2B3C0 UDP Rcv 10.0.0.9        c1d2   Q [0001   D   NOERROR] A      (3)www(4)evil(3)com(0)
+fn parse_windows_dns_line(line: &str) -> Option<DnsQuery> {
+    let (before, packet) = line.split_once(" PACKET ")?;
+    let fields: Vec<&str> = packet.split_whitespace().collect();
+    // only the queries received from clients, not their responses or the forwarded queries
+    if fields.len() < 6 || fields[2] != "Rcv" || fields[5] == "R" {
+        return None;
+    }
+    let client: &str = fields[3];
+    client.parse::<IpAddr>().ok()?;
+    let name: String = decode_labels(fields.last()?)?;
+    let timestamp: Vec<&str> = before.split_whitespace().collect();
+    Some(DnsQuery {
+        // the timestamp is followed by the ID of the thread
+        timestamp: match timestamp.split_last() {
+            Some((_, timestamp)) if !timestamp.is_empty() => Some(timestamp.join(" ")),
+            _ => None,
+        },
+        client: client.to_string(),
+        name,
+    })
+}
+
+pub fn read_windows_dns_log<F: FnMut(InputValue)>(
+    reader: impl BufRead,
+    callback: F,
+) -> Result<(), io::Error> {
+    read_dns_log(reader, parse_windows_dns_line, callback)
+}
+
+#[cfg(test)]
+fn read_values(
+    read: impl FnOnce(&mut dyn FnMut(InputValue)) -> Result<(), io::Error>,
+) -> Vec<(String, String)> {
+    let mut values: Vec<(String, String)> = Vec::new();
+    read(&mut |value| values.push((value.value.clone(), value.context_string()))).unwrap();
+    values
+}
+
+#[cfg(test)]
+fn query_values(timestamp: &str, client: &str, name: &str) -> Vec<(String, String)> {
+    vec![
+        (
+            client.to_string(),
+            format!("timestamp={};client={};field=client", timestamp, client),
+        ),
+        (
+            name.to_string(),
+            format!("timestamp={};client={};field=query", timestamp, client),
+        ),
+    ]
+}
+
+#[test]
+fn test_read_bind_log() {
+    let log: &str = "02-May-2024 10:04:16.123 queries: info: client @0x7f2b1c0 10.0.0.9#52345 (www.evil.com): query: www.evil.com IN A +E(0)K (10.0.0.1)\n\
+        02-May-2024 10:04:17.000 client 2001:db8::9#5353: query: bad.org. IN AAAA + (10.0.0.1)\n\
+        02-May-2024 10:04:18.000 general: info: zone evil.com/IN: loaded serial 1\n";
+    let mut expected = query_values("02-May-2024 10:04:16.123", "10.0.0.9", "www.evil.com");
+    expected.extend(query_values(
+        "02-May-2024 10:04:17.000",
+        "2001:db8::9",
+        "bad.org",
+    ));
+    assert_eq!(
+        read_values(|callback| read_bind_log(log.as_bytes(), callback)),
+        expected
+    );
+}
+
+#[test]
+fn test_read_dnsmasq_log() {
+    let log: &str = "May  2 10:04:16 dnsmasq[1234]: query[A] www.evil.com from 10.0.0.9\n\
+        May  2 10:04:16 dnsmasq[1234]: forwarded www.evil.com to 8.8.8.8\n\
+        May  2 10:04:16 dnsmasq[1234]: reply www.evil.com is 1.2.3.4\n";
+    assert_eq!(
+        read_values(|callback| read_dnsmasq_log(log.as_bytes(), callback)),
+        query_values("May 2 10:04:16", "10.0.0.9", "www.evil.com")
+    );
+    assert!(read_dnsmasq_log("not a log line\n".as_bytes(), |_| ()).is_err());
+}
+
+#[test]
+fn test_read_unbound_log() {
+    let log: &str = "[1714644256] unbound[1234:0] info: 10.0.0.9 www.evil.com. A IN\n\
+        [1714644257] unbound[1234:0] info: start of service (unbound 1.17.1).\n";
+    assert_eq!(
+        read_values(|callback| read_unbound_log(log.as_bytes(), callback)),
+        query_values("1714644256", "10.0.0.9", "www.evil.com")
+    );
+}
+
+#[test]
+fn test_read_windows_dns_log() {
+    let log: &str = "DNS Server log file creation at 5/2/2024 10:00:00 AM\n\
+        5/2/2024 10:04:16 AM 0A3C PACKET  000000B1E1A2B3C0 UDP Rcv 10.0.0.9        c1d2   Q [0001   D   NOERROR] A      (3)www(4)evil(3)com(0)\n\
+        5/2/2024 10:04:16 AM 0A3C PACKET  000000B1E1A2B3C0 UDP Snd 10.0.0.9        c1d2 R Q [8081   DR  NOERROR] A      (3)www(4)evil(3)com(0)\n";
+    assert_eq!(
+        read_values(|callback| read_windows_dns_log(log.as_bytes(), callback)),
+        query_values("5/2/2024 10:04:16 AM", "10.0.0.9", "www.evil.com")
+    );
+    assert_eq!(
+        decode_labels("(3)www(4)evil(3)com(0)").unwrap(),
+        "www.evil.com"
+    );
+    assert!(decode_labels("www.evil.com").is_none());
+}
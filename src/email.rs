@@ -0,0 +1,378 @@
+use crate::atom_type::{detect_atom_type, AtomType};
+use crate::extract::IndicatorExtractor;
+use crate::{to_hex, InputValue};
+use mail_parser::mailbox::mbox::MessageIterator;
+use mail_parser::{Address, Host, Message, MessageParser, MimeHeaders, PartType};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::io::{self, BufRead};
+use std::net::IpAddr;
+
+// reads an email in the .eml format, or the emails of a mbox file
+pub fn read_email<F: FnMut(InputValue)>(
+    mut reader: impl BufRead,
+    mut callback: F,
+) -> Result<(), io::Error> {
+    let mut input: Vec<u8> = Vec::new();
+    reader.read_to_end(&mut input)?;
+    let parser = MessageParser::default();
+    let extractor = IndicatorExtractor::new();
+    if !input.starts_with(b"From ") {
+        return match parser.parse(&input[..]) {
+            Some(message) if !message.headers().is_empty() => {
+                read_message(&message, 1, &extractor, &mut callback);
+                Ok(())
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The input is not an email message",
+            )),
+        };
+    }
+    for (index, entry) in MessageIterator::new(&input[..]).enumerate() {
+        let entry = entry.map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("message {}: invalid mbox entry", index + 1),
+            )
+        })?;
+        if let Some(message) = parser.parse(entry.contents()) {
+            read_message(&message, index + 1, &extractor, &mut callback);
+        }
+    }
+    Ok(())
+}
+
+fn read_message<F: FnMut(InputValue)>(
+    message: &Message,
+    number: usize,
+    extractor: &IndicatorExtractor,
+    callback: &mut F,
+) {
+    let mut context: Vec<(String, String)> = vec![("message".to_string(), number.to_string())];
+    if let Some(message_id) = message.message_id() {
+        context.push(("message_id".to_string(), message_id.to_string()));
+    }
+    read_message_parts(message, "", &context, extractor, callback);
+}
+
+fn emit<F: FnMut(InputValue)>(
+    callback: &mut F,
+    context: &[(String, String)],
+    value: String,
+    part: &str,
+    filename: Option<&str>,
+    field: &str,
+) {
+    let mut context: Vec<(String, String)> = context.to_vec();
+    context.push(("part".to_string(), part.to_string()));
+    if let Some(filename) = filename {
+        context.push(("filename".to_string(), filename.to_string()));
+    }
+    context.push(("field".to_string(), field.to_string()));
+    callback(InputValue::with_context(value, context));
+}
+
+// the parts of attached messages, such as a reported phishing email, are read as well, their
+// ids being prefixed by the id of the attachment, e.g. 2.headers or 2.1
+fn read_message_parts<F: FnMut(InputValue)>(
+    message: &Message,
+    prefix: &str,
+    context: &[(String, String)],
+    extractor: &IndicatorExtractor,
+    callback: &mut F,
+) {
+    let headers_id: String = format!("{}headers", prefix);
+    for (field, address) in [
+        ("from", message.from()),
+        ("sender", message.sender()),
+        ("reply_to", message.reply_to()),
+    ] {
+        for address in address.into_iter().flat_map(Address::iter) {
+            let address: &str = match address.address() {
+                Some(address) if address.contains('@') => address.trim(),
+                _ => continue,
+            };
+            emit(
+                callback,
+                context,
+                address.to_string(),
+                &headers_id,
+                None,
+                field,
+            );
+            if let Some((_, domain)) = address.rsplit_once('@') {
+                emit(
+                    callback,
+                    context,
+                    domain.to_lowercase(),
+                    &headers_id,
+                    None,
+                    &format!("{}.domain", field),
+                );
+            }
+        }
+    }
+    // the IPs of the relays written in the Received headers
+    for received in message
+        .header_values("Received")
+        .filter_map(|value| value.as_received())
+    {
+        let mut ips: Vec<IpAddr> = received.from_ip.into_iter().collect();
+        if let Some(Host::IpAddr(ip)) = received.from {
+            if !ips.contains(&ip) {
+                ips.push(ip);
+            }
+        }
+        for ip in ips {
+            emit(
+                callback,
+                context,
+                ip.to_string(),
+                &headers_id,
+                None,
+                "received",
+            );
+        }
+    }
+
+    for (index, part) in message.parts.iter().enumerate() {
+        let part_id: String = format!("{}{}", prefix, index);
+        if message.attachments.contains(&index) {
+            if part.is_multipart() {
+                continue;
+            }
+            let contents: &[u8] = part.contents();
+            let filename: Option<&str> = part.attachment_name();
+            for (hash, field) in [
+                (to_hex(&Md5::digest(contents)), "attachment.md5"),
+                (to_hex(&Sha1::digest(contents)), "attachment.sha1"),
+                (to_hex(&Sha256::digest(contents)), "attachment.sha256"),
+            ] {
+                emit(callback, context, hash, &part_id, filename, field);
+            }
+            if let PartType::Message(attached) = &part.body {
+                read_message_parts(
+                    attached,
+                    &format!("{}.", part_id),
+                    context,
+                    extractor,
+                    callback,
+                );
+            }
+            continue;
+        }
+        let text: String = match &part.body {
+            PartType::Text(text) => text.to_string(),
+            // the links of HTML bodies are written with escaped ampersands
+            PartType::Html(html) => html.replace("&amp;", "&"),
+            _ => continue,
+        };
+        for value in extractor.extract(&text) {
+            if detect_atom_type(&value) == AtomType::Url {
+                emit(callback, context, value, &part_id, None, "url");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_read_email() {
+    let email: &str = "Received: from mail.evil.com (mail.evil.com [1.2.3.4])\r\n\tby mx.corp.local with ESMTP id 42; Thu, 2 May 2024 10:04:16 +0200\r\n\
+        From: \"Support\" <support@Evil.com>\r\n\
+        Reply-To: billing@bad.org\r\n\
+        Message-ID: <1234@evil.com>\r\n\
+        Subject: Invoice\r\n\
+        MIME-Version: 1.0\r\n\
+        Content-Type: multipart/mixed; boundary=\"b1\"\r\n\
+        \r\n\
+        --b1\r\n\
+        Content-Type: text/html\r\n\
+        \r\n\
+        <a href=\"http://evil.com/pay?a=1&amp;b=2\">Pay</a>\r\n\
+        --b1\r\n\
+        Content-Type: application/octet-stream; name=\"invoice.bin\"\r\n\
+        Content-Disposition: attachment; filename=\"invoice.bin\"\r\n\
+        Content-Transfer-Encoding: base64\r\n\
+        \r\n\
+        YWJj\r\n\
+        --b1--\r\n";
+    let mut values: Vec<(String, String)> = Vec::new();
+    read_email(email.as_bytes(), |value| {
+        values.push((value.value.clone(), value.context_string()))
+    })
+    .unwrap();
+    let headers_context = |field: &str| {
+        format!(
+            "message=1;message_id=1234@evil.com;part=headers;field={}",
+            field
+        )
+    };
+    let attachment_context = |field: &str| {
+        format!(
+            "message=1;message_id=1234@evil.com;part=2;filename=invoice.bin;field={}",
+            field
+        )
+    };
+    assert_eq!(
+        values,
+        vec![
+            ("support@Evil.com".to_string(), headers_context("from")),
+            ("evil.com".to_string(), headers_context("from.domain")),
+            ("billing@bad.org".to_string(), headers_context("reply_to")),
+            ("bad.org".to_string(), headers_context("reply_to.domain")),
+            ("1.2.3.4".to_string(), headers_context("received")),
+            (
+                "http://evil.com/pay?a=1&b=2".to_string(),
+                "message=1;message_id=1234@evil.com;part=1;field=url".to_string()
+            ),
+            (
+                "900150983cd24fb0d6963f7d28e17f72".to_string(),
+                attachment_context("attachment.md5")
+            ),
+            (
+                "a9993e364706816aba3e25717850c26c9cd0d89d".to_string(),
+                attachment_context("attachment.sha1")
+            ),
+            (
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string(),
+                attachment_context("attachment.sha256")
+            ),
+        ]
+    );
+
+    assert!(read_email("".as_bytes(), |_| ()).is_err());
+}
+
+#[test]
+fn test_read_mbox() {
+    let mbox: &str = "From support@evil.com Thu May  2 10:04:16 2024\n\
+        From: support@evil.com\n\
+        Subject: First\n\
+        \n\
+        See https://evil.com/a\n\
+        \n\
+        From billing@bad.org Thu May  2 10:05:16 2024\n\
+        From: billing@bad.org\n\
+        Message-ID: <5678@bad.org>\n\
+        Subject: Second\n\
+        \n\
+        >From the team: http://bad.org/b\n";
+    let mut values: Vec<(String, String)> = Vec::new();
+    read_email(mbox.as_bytes(), |value| {
+        values.push((value.value.clone(), value.context_string()))
+    })
+    .unwrap();
+    assert_eq!(
+        values,
+        vec![
+            (
+                "support@evil.com".to_string(),
+                "message=1;part=headers;field=from".to_string()
+            ),
+            (
+                "evil.com".to_string(),
+                "message=1;part=headers;field=from.domain".to_string()
+            ),
+            (
+                "https://evil.com/a".to_string(),
+                "message=1;part=0;field=url".to_string()
+            ),
+            (
+                "billing@bad.org".to_string(),
+                "message=2;message_id=5678@bad.org;part=headers;field=from".to_string()
+            ),
+            (
+                "bad.org".to_string(),
+                "message=2;message_id=5678@bad.org;part=headers;field=from.domain".to_string()
+            ),
+            (
+                "http://bad.org/b".to_string(),
+                "message=2;message_id=5678@bad.org;part=0;field=url".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_read_forwarded_email() {
+    let email: &str = "From: analyst@corp.local\r\n\
+        Message-ID: <report@corp.local>\r\n\
+        Subject: Fwd: Invoice\r\n\
+        MIME-Version: 1.0\r\n\
+        Content-Type: multipart/mixed; boundary=\"outer\"\r\n\
+        \r\n\
+        --outer\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        Please have a look.\r\n\
+        --outer\r\n\
+        Content-Type: message/rfc822\r\n\
+        Content-Disposition: attachment; filename=\"phish.eml\"\r\n\
+        \r\n\
+        Received: from mail.evil.com (mail.evil.com [1.2.3.4])\r\n\tby mx.corp.local with ESMTP id 42; Thu, 2 May 2024 10:04:16 +0200\r\n\
+        From: support@evil.com\r\n\
+        Subject: Invoice\r\n\
+        MIME-Version: 1.0\r\n\
+        Content-Type: multipart/mixed; boundary=\"inner\"\r\n\
+        \r\n\
+        --inner\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        Pay at http://evil.com/pay\r\n\
+        --inner\r\n\
+        Content-Type: application/octet-stream\r\n\
+        Content-Disposition: attachment; filename=\"invoice.bin\"\r\n\
+        Content-Transfer-Encoding: base64\r\n\
+        \r\n\
+        YWJj\r\n\
+        --inner--\r\n\
+        \r\n\
+        --outer--\r\n";
+    let mut values: Vec<(String, String)> = Vec::new();
+    read_email(email.as_bytes(), |value| {
+        values.push((value.value.clone(), value.context_string()))
+    })
+    .unwrap();
+    // the hashes of the attached message itself are left out
+    let values: Vec<(String, String)> = values
+        .into_iter()
+        .filter(|(_, context)| !context.contains("part=2;"))
+        .collect();
+    let context = |part: &str, field: &str| {
+        format!(
+            "message=1;message_id=report@corp.local;part={};field={}",
+            part, field
+        )
+    };
+    let attachment_context = |field: &str| {
+        format!(
+            "message=1;message_id=report@corp.local;part=2.2;filename=invoice.bin;field={}",
+            field
+        )
+    };
+    assert_eq!(
+        values,
+        vec![
+            ("analyst@corp.local".to_string(), context("headers", "from")),
+            ("corp.local".to_string(), context("headers", "from.domain")),
+            ("support@evil.com".to_string(), context("2.headers", "from")),
+            ("evil.com".to_string(), context("2.headers", "from.domain")),
+            ("1.2.3.4".to_string(), context("2.headers", "received")),
+            ("http://evil.com/pay".to_string(), context("2.1", "url")),
+            (
+                "900150983cd24fb0d6963f7d28e17f72".to_string(),
+                attachment_context("attachment.md5")
+            ),
+            (
+                "a9993e364706816aba3e25717850c26c9cd0d89d".to_string(),
+                attachment_context("attachment.sha1")
+            ),
+            (
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string(),
+                attachment_context("attachment.sha256")
+            ),
+        ]
+    );
+}
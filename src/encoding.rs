@@ -0,0 +1,157 @@
+use encoding_rs::{Decoder, DecoderResult, Encoding, UTF_8};
+use std::io::{self, BufRead, Read};
+
+pub fn parse_encoding(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.trim().as_bytes()).ok_or(format!("Unknown encoding {}", label))
+}
+
+// decodes text input to UTF-8, using its BOM when it has one and no encoding is given,
+// and replacing the bytes which can't be decoded with U+FFFD
+pub struct DecodingReader<R: BufRead> {
+    reader: R,
+    decoder: Decoder,
+    // decoded text of the current line, and whether some of its bytes couldn't be decoded
+    line: String,
+    malformed: bool,
+    line_number: usize,
+    replaced_lines: Vec<usize>,
+    output: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl<R: BufRead> DecodingReader<R> {
+    pub fn new(reader: R, encoding: Option<&'static Encoding>) -> Self {
+        let decoder: Decoder = match encoding {
+            Some(encoding) => encoding.new_decoder_with_bom_removal(),
+            None => UTF_8.new_decoder(),
+        };
+        DecodingReader {
+            reader,
+            decoder,
+            line: String::new(),
+            malformed: false,
+            line_number: 0,
+            replaced_lines: Vec::new(),
+            output: Vec::new(),
+            position: 0,
+            finished: false,
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.line_number += 1;
+        if self.malformed {
+            self.replaced_lines.push(self.line_number);
+        }
+        self.output.extend_from_slice(self.line.as_bytes());
+        self.line.clear();
+        self.malformed = false;
+    }
+
+    fn push_text(&mut self, text: &str) {
+        for segment in text.split_inclusive('\n') {
+            self.line.push_str(segment);
+            if segment.ends_with('\n') {
+                self.end_line();
+            }
+        }
+    }
+
+    fn decode_chunk(&mut self) -> Result<(), io::Error> {
+        let input: &[u8] = self.reader.fill_buf()?;
+        let last: bool = input.is_empty();
+        let capacity: usize = self
+            .decoder
+            .max_utf8_buffer_length_without_replacement(input.len())
+            .unwrap_or(input.len() * 3);
+        let mut text: String = String::with_capacity(capacity.max(16));
+        let (result, read) = self
+            .decoder
+            .decode_to_string_without_replacement(input, &mut text, last);
+        self.reader.consume(read);
+        self.push_text(&text);
+        match result {
+            // the line is kept, so that a quoted field spanning several lines stays closed
+            DecoderResult::Malformed(_, _) => {
+                self.line.push(char::REPLACEMENT_CHARACTER);
+                self.malformed = true;
+            }
+            DecoderResult::InputEmpty if last => {
+                if !self.line.is_empty() {
+                    self.end_line();
+                }
+                self.finished = true;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    // numbers of the lines in which undecodable bytes were replaced
+    pub fn replaced_lines(&self) -> &[usize] {
+        &self.replaced_lines
+    }
+}
+
+impl<R: BufRead> Read for DecodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position >= self.output.len() {
+            if self.finished {
+                return Ok(0);
+            }
+            self.output.clear();
+            self.position = 0;
+            self.decode_chunk()?;
+        }
+        let length: usize = buf.len().min(self.output.len() - self.position);
+        buf[..length].copy_from_slice(&self.output[self.position..self.position + length]);
+        self.position += length;
+        Ok(length)
+    }
+}
+
+#[cfg(test)]
+fn decode(input: &[u8], encoding: Option<&'static Encoding>) -> (String, Vec<usize>) {
+    let mut reader = DecodingReader::new(input, encoding);
+    let mut output: String = String::new();
+    reader.read_to_string(&mut output).unwrap();
+    (output, reader.replaced_lines().to_vec())
+}
+
+#[test]
+fn test_decoding_reader() {
+    // UTF-16 with a BOM
+    let utf16: Vec<u8> = [0xff, 0xfe]
+        .into_iter()
+        .chain(
+            "évil.com\r\n10.0.0.1"
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes),
+        )
+        .collect();
+    assert_eq!(
+        decode(&utf16, None),
+        ("évil.com\r\n10.0.0.1".to_string(), Vec::new())
+    );
+    assert_eq!(
+        decode(b"\xef\xbb\xbfevil.com\n", None),
+        ("evil.com\n".to_string(), Vec::new())
+    );
+    assert_eq!(
+        decode(
+            b"caf\xe9.com\n",
+            Some(parse_encoding("windows-1252").unwrap())
+        ),
+        ("café.com\n".to_string(), Vec::new())
+    );
+    // the invalid UTF-8 bytes are replaced
+    assert_eq!(
+        decode(b"evil.com\ncaf\xe9.com\n10.0.0.1\n\xff", None),
+        (
+            "evil.com\ncaf\u{fffd}.com\n10.0.0.1\n\u{fffd}".to_string(),
+            vec![2, 4]
+        )
+    );
+    assert!(parse_encoding("unknown").is_err());
+}
@@ -0,0 +1,861 @@
+use crate::InputValue;
+use std::io::{self, BufRead};
+
+const FILE_HEADER_LEN: usize = 4096;
+const CHUNK_LEN: usize = 65536;
+const CHUNK_HEADER_LEN: usize = 512;
+const RECORD_HEADER_LEN: usize = 24;
+const RECORD_SIGNATURE: [u8; 4] = [0x2a, 0x2a, 0x00, 0x00];
+
+// templates and embedded binary XML are nested, corrupted records could nest them endlessly
+const MAX_DEPTH: usize = 64;
+// the substitution values of a template can be used several times, and hold templates whose
+// values are used several times, so corrupted records could expand exponentially
+const MAX_EXPANDED_SIZE: usize = 1 << 20;
+
+// fields of the Sysmon events holding indicators
+const INDICATOR_FIELDS: [&str; 5] = [
+    "DestinationIp",
+    "QueryName",
+    "Image",
+    "ParentImage",
+    "ImageLoaded",
+];
+// hashes written as SHA1=...,MD5=...,SHA256=...,IMPHASH=...
+const HASHES_FIELDS: [&str; 2] = ["Hashes", "Hash"];
+
+// 100 nanoseconds intervals between 1601-01-01 and 1970-01-01
+const FILETIME_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Element(Element),
+    Text(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Node>,
+}
+
+impl Element {
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter_map(move |node| match node {
+            Node::Element(element) if element.name == name => Some(element),
+            _ => None,
+        })
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find_map(|node| match node {
+            Node::Element(element) if element.name == name => Some(element),
+            _ => None,
+        })
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|node| match node {
+                Node::Text(text) => Some(text.as_str()),
+                Node::Element(_) => None,
+            })
+            .collect()
+    }
+}
+
+// values substituted in the template of a record
+enum Substitution {
+    Null,
+    Text(String),
+    Xml(Vec<Node>),
+}
+
+// offsets of the binary XML are relative to the start of its chunk
+struct Cursor<'a> {
+    chunk: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn at(chunk: &'a [u8], position: usize) -> Self {
+        Cursor { chunk, position }
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes: &[u8] = self
+            .chunk
+            .get(self.position..self.position.checked_add(len)?)?;
+        self.position += len;
+        Some(bytes)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.chunk.get(self.position).copied()
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(le_uint(self.bytes(2)?) as u16)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(le_uint(self.bytes(4)?) as u32)
+    }
+
+    fn utf16(&mut self, len: usize) -> Option<String> {
+        Some(read_utf16(self.bytes(len.checked_mul(2)?)?))
+    }
+}
+
+fn le_uint(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |value, byte| value << 8 | *byte as u64)
+}
+
+fn read_utf16(bytes: &[u8]) -> String {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]));
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+// reads the event records of an EVTX file, extracting the indicators of Sysmon events
+pub fn read_evtx<F: FnMut(InputValue)>(
+    mut reader: impl BufRead,
+    mut callback: F,
+) -> Result<(), io::Error> {
+    let mut header: Vec<u8> = vec![0; FILE_HEADER_LEN];
+    reader.read_exact(&mut header)?;
+    if !header.starts_with(b"ElfFile\0") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Not an EVTX file",
+        ));
+    }
+    let mut chunk: Vec<u8> = vec![0; CHUNK_LEN];
+    loop {
+        let mut read: usize = 0;
+        while read < CHUNK_LEN {
+            match reader.read(&mut chunk[read..])? {
+                0 => return Ok(()),
+                n => read += n,
+            }
+        }
+        // unused chunks at the end of the file are zeroed
+        if chunk.starts_with(b"ElfChnk\0") {
+            read_chunk(&chunk, &mut callback);
+        }
+    }
+}
+
+fn read_chunk<F: FnMut(InputValue)>(chunk: &[u8], callback: &mut F) {
+    let free_space_offset: usize = (le_uint(&chunk[48..52]) as usize).min(chunk.len());
+    let mut offset: usize = CHUNK_HEADER_LEN;
+    while offset + RECORD_HEADER_LEN <= free_space_offset
+        && chunk[offset..offset + 4] == RECORD_SIGNATURE
+    {
+        let size: usize = le_uint(&chunk[offset + 4..offset + 8]) as usize;
+        if size < RECORD_HEADER_LEN || offset + size > free_space_offset {
+            break;
+        }
+        let record_id: u64 = le_uint(&chunk[offset + 8..offset + 16]);
+        let written: u64 = le_uint(&chunk[offset + 16..offset + 24]);
+        // corrupted records are skipped
+        let mut cursor = Cursor::at(chunk, offset + RECORD_HEADER_LEN);
+        let mut budget: usize = MAX_EXPANDED_SIZE;
+        if let Some(nodes) = parse_nodes(&mut cursor, &[], 0, &mut budget) {
+            for node in nodes {
+                match node {
+                    Node::Element(event) if event.name == "Event" => {
+                        extract_event(&event, record_id, written, callback)
+                    }
+                    _ => (),
+                }
+            }
+        }
+        offset += size;
+    }
+}
+
+fn extract_event<F: FnMut(InputValue)>(
+    event: &Element,
+    record_id: u64,
+    written: u64,
+    callback: &mut F,
+) {
+    let system: Option<&Element> = event.child("System");
+    let system_text = |name: &str| {
+        system
+            .and_then(|system| system.child(name))
+            .map(Element::text)
+    };
+    let timestamp: String = system
+        .and_then(|system| system.child("TimeCreated"))
+        .and_then(|time_created| time_created.attribute("SystemTime"))
+        .map_or_else(|| format_filetime(written), str::to_string);
+    let mut context: Vec<(String, String)> = Vec::new();
+    if let Some(event_id) = system_text("EventID") {
+        context.push(("event_id".to_string(), event_id));
+    }
+    if let Some(computer) = system_text("Computer") {
+        context.push(("computer".to_string(), computer));
+    }
+    context.push(("timestamp".to_string(), timestamp));
+    context.push((
+        "record_id".to_string(),
+        system_text("EventRecordID").unwrap_or_else(|| record_id.to_string()),
+    ));
+
+    let mut emit = |value: &str, field: &str| {
+        let value: &str = value.trim();
+        if value.is_empty() || value == "-" {
+            return;
+        }
+        let mut context: Vec<(String, String)> = context.clone();
+        context.push(("field".to_string(), field.to_string()));
+        callback(InputValue::with_context(value.to_string(), context));
+    };
+    let data = event
+        .children("EventData")
+        .flat_map(|event_data| event_data.children("Data"));
+    for data in data {
+        let name: &str = data.attribute("Name").unwrap_or_default();
+        if HASHES_FIELDS.contains(&name) {
+            for hash in data.text().split(',') {
+                if let Some((algorithm, hash)) = hash.split_once('=') {
+                    emit(hash, &algorithm.trim().to_lowercase());
+                }
+            }
+        } else if INDICATOR_FIELDS.contains(&name) {
+            emit(&data.text(), name);
+        }
+    }
+}
+
+// binary XML tokens up to the end of the stream or of the parent element
+fn parse_nodes(
+    cursor: &mut Cursor,
+    substitutions: &[Substitution],
+    depth: usize,
+    budget: &mut usize,
+) -> Option<Vec<Node>> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    let mut nodes: Vec<Node> = Vec::new();
+    loop {
+        spend(budget, 1)?;
+        let token: u8 = cursor.u8()?;
+        match token & 0x0f {
+            // end of stream and end of element
+            0x00 | 0x04 => return Some(nodes),
+            0x01 => nodes.push(Node::Element(parse_element(
+                cursor,
+                token & 0x40 != 0,
+                substitutions,
+                depth,
+                budget,
+            )?)),
+            0x05 | 0x07 | 0x08 | 0x09 | 0x0d | 0x0e => {
+                nodes.extend(parse_content(cursor, token, substitutions, budget)?)
+            }
+            // processing instructions
+            0x0a => {
+                let name_offset: u32 = cursor.u32()?;
+                read_name(cursor, name_offset)?;
+            }
+            0x0b => {
+                let len: usize = cursor.u16()? as usize;
+                cursor.bytes(len * 2)?;
+            }
+            // the substitution values of a template instance end its fragment
+            0x0c => {
+                nodes.extend(parse_template_instance(cursor, depth, budget)?);
+                return Some(nodes);
+            }
+            // fragment header
+            0x0f => {
+                cursor.bytes(3)?;
+            }
+            _ => return None,
+        }
+    }
+}
+
+fn parse_element(
+    cursor: &mut Cursor,
+    has_attributes: bool,
+    substitutions: &[Substitution],
+    depth: usize,
+    budget: &mut usize,
+) -> Option<Element> {
+    // dependency identifier and data size
+    cursor.bytes(6)?;
+    let name_offset: u32 = cursor.u32()?;
+    let mut element = Element {
+        name: read_name(cursor, name_offset)?,
+        attributes: Vec::new(),
+        children: Vec::new(),
+    };
+    if has_attributes {
+        // size of the attribute list
+        cursor.bytes(4)?;
+    }
+    loop {
+        let token: u8 = cursor.u8()?;
+        match token & 0x0f {
+            0x06 => {
+                let name_offset: u32 = cursor.u32()?;
+                let name: String = read_name(cursor, name_offset)?;
+                let mut value: Option<String> = None;
+                while let Some(token) = cursor.peek() {
+                    if !matches!(token & 0x0f, 0x05 | 0x07 | 0x08 | 0x09 | 0x0d | 0x0e) {
+                        break;
+                    }
+                    cursor.u8()?;
+                    for node in parse_content(cursor, token, substitutions, budget)? {
+                        if let Node::Text(text) = node {
+                            value.get_or_insert_with(String::new).push_str(&text);
+                        }
+                    }
+                }
+                // attributes whose optional substitution is empty are left out
+                if let Some(value) = value {
+                    element.attributes.push((name, value));
+                }
+            }
+            0x02 => {
+                element.children = parse_nodes(cursor, substitutions, depth + 1, budget)?;
+                return Some(element);
+            }
+            0x03 => return Some(element),
+            _ => return None,
+        }
+    }
+}
+
+fn parse_content(
+    cursor: &mut Cursor,
+    token: u8,
+    substitutions: &[Substitution],
+    budget: &mut usize,
+) -> Option<Vec<Node>> {
+    let text: String = match token & 0x0f {
+        // values are always strings outside of substitutions
+        0x05 => {
+            if cursor.u8()? != 0x01 {
+                return None;
+            }
+            let len: usize = cursor.u16()? as usize;
+            cursor.utf16(len)?
+        }
+        0x07 => {
+            let len: usize = cursor.u16()? as usize;
+            cursor.utf16(len)?
+        }
+        0x08 => char::from_u32(cursor.u16()? as u32)?.to_string(),
+        0x09 => {
+            let name_offset: u32 = cursor.u32()?;
+            match read_name(cursor, name_offset)?.as_str() {
+                "amp" => "&".to_string(),
+                "lt" => "<".to_string(),
+                "gt" => ">".to_string(),
+                "quot" => "\"".to_string(),
+                "apos" => "'".to_string(),
+                name => format!("&{};", name),
+            }
+        }
+        _ => {
+            let index: usize = cursor.u16()? as usize;
+            // type of the value
+            cursor.u8()?;
+            return Some(match substitutions.get(index) {
+                Some(Substitution::Text(text)) => {
+                    spend(budget, text.len())?;
+                    vec![Node::Text(text.clone())]
+                }
+                Some(Substitution::Xml(nodes)) => {
+                    spend(budget, expanded_size(nodes))?;
+                    nodes.clone()
+                }
+                Some(Substitution::Null) | None => Vec::new(),
+            });
+        }
+    };
+    Some(vec![Node::Text(text)])
+}
+
+// names are written once in the chunk, where they are first used, and then referenced
+fn read_name(cursor: &mut Cursor, offset: u32) -> Option<String> {
+    let offset: usize = offset as usize;
+    if offset == cursor.position {
+        // offset of the next name and hash
+        cursor.bytes(6)?;
+        let len: usize = cursor.u16()? as usize;
+        let name: String = cursor.utf16(len)?;
+        cursor.bytes(2)?;
+        return Some(name);
+    }
+    let mut name_cursor = Cursor::at(cursor.chunk, offset + 6);
+    let len: usize = name_cursor.u16()? as usize;
+    name_cursor.utf16(len)
+}
+
+fn parse_template_instance(
+    cursor: &mut Cursor,
+    depth: usize,
+    budget: &mut usize,
+) -> Option<Vec<Node>> {
+    // unknown byte and template identifier
+    cursor.bytes(5)?;
+    let definition_offset: usize = cursor.u32()? as usize;
+    let data_len: usize = Cursor::at(cursor.chunk, definition_offset + 20).u32()? as usize;
+    // the definition follows the instance when the template is first used in the chunk
+    if definition_offset == cursor.position {
+        cursor.bytes(24 + data_len)?;
+    }
+    let count: usize = cursor.u32()? as usize;
+    let mut descriptors: Vec<(usize, u8)> = Vec::new();
+    for _ in 0..count {
+        let size: usize = cursor.u16()? as usize;
+        let value_type: u8 = cursor.u8()?;
+        cursor.u8()?;
+        descriptors.push((size, value_type));
+    }
+    let mut substitutions: Vec<Substitution> = Vec::new();
+    for (size, value_type) in descriptors {
+        let offset: usize = cursor.position;
+        let bytes: &[u8] = cursor.bytes(size)?;
+        substitutions.push(match value_type {
+            0x21 => {
+                let mut xml_cursor = Cursor::at(&cursor.chunk[..offset + size], offset);
+                match parse_nodes(&mut xml_cursor, &[], depth + 1, budget) {
+                    Some(nodes) => Substitution::Xml(nodes),
+                    None if *budget == 0 => return None,
+                    None => Substitution::Null,
+                }
+            }
+            _ => match format_value(bytes, value_type) {
+                Some(text) => Substitution::Text(text),
+                None => Substitution::Null,
+            },
+        });
+    }
+    let mut definition = Cursor::at(cursor.chunk, definition_offset + 24);
+    parse_nodes(&mut definition, &substitutions, depth + 1, budget)
+}
+
+// the size of the expanded nodes is spent from the budget of the record, which is skipped
+// once it is exhausted
+fn spend(budget: &mut usize, size: usize) -> Option<()> {
+    match budget.checked_sub(size) {
+        Some(remaining) => {
+            *budget = remaining;
+            Some(())
+        }
+        None => {
+            *budget = 0;
+            None
+        }
+    }
+}
+
+fn expanded_size(nodes: &[Node]) -> usize {
+    nodes
+        .iter()
+        .map(|node| match node {
+            Node::Text(text) => 1 + text.len(),
+            Node::Element(element) => {
+                1 + element.name.len()
+                    + element
+                        .attributes
+                        .iter()
+                        .map(|(name, value)| name.len() + value.len())
+                        .sum::<usize>()
+                    + expanded_size(&element.children)
+            }
+        })
+        .sum()
+}
+
+fn format_value(bytes: &[u8], value_type: u8) -> Option<String> {
+    if bytes.is_empty() {
+        return None;
+    }
+    // arrays of values
+    if value_type & 0x80 != 0 {
+        let item_type: u8 = value_type & 0x7f;
+        let items: Vec<String> = match (item_type, value_size(item_type)) {
+            (0x01, _) => read_utf16(bytes)
+                .split('\0')
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect(),
+            (_, Some(size)) => bytes
+                .chunks_exact(size)
+                .filter_map(|item| format_value(item, item_type))
+                .collect(),
+            (_, None) => return Some(to_hex(bytes)),
+        };
+        return Some(items.join(","));
+    }
+    let value: u64 = le_uint(&bytes[..bytes.len().min(8)]);
+    Some(match (value_type, bytes.len()) {
+        (0x00, _) => return None,
+        (0x01, _) => read_utf16(bytes).trim_end_matches('\0').to_string(),
+        (0x02, _) => String::from_utf8_lossy(bytes)
+            .trim_end_matches('\0')
+            .to_string(),
+        (0x03, 1) => (value as i8).to_string(),
+        (0x05, 2) => (value as i16).to_string(),
+        (0x07, 4) => (value as i32).to_string(),
+        (0x09, 8) => (value as i64).to_string(),
+        (0x04, 1) | (0x06, 2) | (0x08, 4) | (0x0a, 8) => value.to_string(),
+        (0x0b, 4) => f32::from_bits(value as u32).to_string(),
+        (0x0c, 8) => f64::from_bits(value).to_string(),
+        (0x0d, 4) => (value != 0).to_string(),
+        (0x0f, 16) => format!(
+            "{{{:08X}-{:04X}-{:04X}-{}-{}}}",
+            le_uint(&bytes[0..4]),
+            le_uint(&bytes[4..6]),
+            le_uint(&bytes[6..8]),
+            to_hex(&bytes[8..10]),
+            to_hex(&bytes[10..16])
+        ),
+        (0x10, 4 | 8) | (0x14, 4) | (0x15, 8) => format!("0x{:x}", value),
+        (0x11, 8) => format_filetime(value),
+        (0x12, 16) => {
+            let field = |index: usize| le_uint(&bytes[index * 2..index * 2 + 2]);
+            format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+                field(0),
+                field(1),
+                field(3),
+                field(4),
+                field(5),
+                field(6),
+                field(7)
+            )
+        }
+        (0x13, len) if len >= 8 => {
+            let authority: u64 = bytes[2..8]
+                .iter()
+                .fold(0, |authority, byte| authority << 8 | *byte as u64);
+            let mut sid: String = format!("S-{}-{}", bytes[0], authority);
+            for sub_authority in bytes[8..].chunks_exact(4).take(bytes[1] as usize) {
+                sid.push_str(&format!("-{}", le_uint(sub_authority)));
+            }
+            sid
+        }
+        _ => to_hex(bytes),
+    })
+}
+
+fn value_size(value_type: u8) -> Option<usize> {
+    match value_type {
+        0x03 | 0x04 => Some(1),
+        0x05 | 0x06 => Some(2),
+        0x07 | 0x08 | 0x0b | 0x0d | 0x14 => Some(4),
+        0x09 | 0x0a | 0x0c | 0x11 | 0x15 => Some(8),
+        0x0f | 0x12 => Some(16),
+        _ => None,
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+fn format_filetime(filetime: u64) -> String {
+    if filetime < FILETIME_UNIX_EPOCH {
+        return filetime.to_string();
+    }
+    let since_epoch: u64 = filetime - FILETIME_UNIX_EPOCH;
+    let seconds: u64 = since_epoch / 10_000_000;
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:07}Z",
+        year,
+        month,
+        day,
+        seconds % 86400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+        since_epoch % 10_000_000
+    )
+}
+
+// date of a number of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days: i64 = days + 719_468;
+    let era: i64 = days.div_euclid(146_097);
+    let day_of_era: i64 = days.rem_euclid(146_097);
+    let year_of_era: i64 =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year: i64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index: i64 = (5 * day_of_year + 2) / 153;
+    let day: u32 = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month: u32 = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year: i64 = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+#[cfg(test)]
+fn push_name(chunk: &mut Vec<u8>, name: &str) {
+    chunk.extend([0; 6]);
+    chunk.extend((name.encode_utf16().count() as u16).to_le_bytes());
+    chunk.extend(name.encode_utf16().flat_map(u16::to_le_bytes));
+    chunk.extend([0; 2]);
+}
+
+// elements, attributes and entity references followed by their name when it is first used
+#[cfg(test)]
+fn push_named_token(chunk: &mut Vec<u8>, token: &[u8], name: &str, names: &mut Vec<(String, u32)>) {
+    chunk.extend(token);
+    match names.iter().find(|(known, _)| known == name) {
+        Some((_, offset)) => chunk.extend(offset.to_le_bytes()),
+        None => {
+            let offset: u32 = chunk.len() as u32 + 4;
+            names.push((name.to_string(), offset));
+            chunk.extend(offset.to_le_bytes());
+            push_name(chunk, name);
+        }
+    }
+}
+
+#[test]
+fn test_format_filetime() {
+    assert_eq!(
+        format_filetime(133591178561234567),
+        "2024-05-02T10:04:16.1234567Z"
+    );
+    assert_eq!(
+        format_filetime(FILETIME_UNIX_EPOCH),
+        "1970-01-01T00:00:00.0000000Z"
+    );
+    assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    assert_eq!(civil_from_days(11016), (2000, 2, 29));
+}
+
+#[test]
+fn test_read_evtx() {
+    let mut names: Vec<(String, u32)> = Vec::new();
+    let mut chunk: Vec<u8> = b"ElfChnk\0".to_vec();
+    chunk.resize(CHUNK_HEADER_LEN, 0);
+    let record_offset: usize = chunk.len();
+    chunk.extend(RECORD_SIGNATURE);
+    chunk.extend([0; 4]);
+    chunk.extend(7u64.to_le_bytes());
+    chunk.extend(133591178561234567u64.to_le_bytes());
+    chunk.extend([0x0f, 1, 1, 0]);
+    // template instance followed by its definition
+    chunk.extend([0x0c, 1, 0, 0, 0, 0]);
+    let definition_offset: usize = chunk.len() + 4;
+    chunk.extend((definition_offset as u32).to_le_bytes());
+    chunk.extend([0; 20]);
+    chunk.extend([0; 4]);
+    let data_offset: usize = chunk.len();
+    chunk.extend([0x0f, 1, 1, 0]);
+    let open =
+        |chunk: &mut Vec<u8>, names: &mut Vec<(String, u32)>, name: &str, attributes: bool| {
+            let token: u8 = if attributes { 0x41 } else { 0x01 };
+            push_named_token(chunk, &[token, 0xff, 0xff, 0, 0, 0, 0], name, names);
+            if attributes {
+                chunk.extend([0; 4]);
+            }
+        };
+    let substitution = |chunk: &mut Vec<u8>, token: u8, index: u16, value_type: u8| {
+        chunk.push(token);
+        chunk.extend(index.to_le_bytes());
+        chunk.push(value_type);
+    };
+    let data = |chunk: &mut Vec<u8>, names: &mut Vec<(String, u32)>, name: &str, index: u16| {
+        open(chunk, names, "Data", true);
+        push_named_token(chunk, &[0x06], "Name", names);
+        chunk.extend([0x05, 0x01]);
+        chunk.extend((name.len() as u16).to_le_bytes());
+        chunk.extend(name.encode_utf16().flat_map(u16::to_le_bytes));
+        chunk.push(0x02);
+        substitution(chunk, 0x0e, index, 0x01);
+        chunk.push(0x04);
+    };
+    open(&mut chunk, &mut names, "Event", false);
+    chunk.push(0x02);
+    open(&mut chunk, &mut names, "System", false);
+    chunk.push(0x02);
+    open(&mut chunk, &mut names, "EventID", false);
+    chunk.push(0x02);
+    substitution(&mut chunk, 0x0d, 0, 0x06);
+    chunk.push(0x04);
+    open(&mut chunk, &mut names, "TimeCreated", true);
+    push_named_token(&mut chunk, &[0x06], "SystemTime", &mut names);
+    substitution(&mut chunk, 0x0e, 1, 0x11);
+    chunk.push(0x03);
+    open(&mut chunk, &mut names, "EventRecordID", false);
+    chunk.push(0x02);
+    substitution(&mut chunk, 0x0d, 2, 0x0a);
+    chunk.push(0x04);
+    open(&mut chunk, &mut names, "Computer", false);
+    chunk.push(0x02);
+    substitution(&mut chunk, 0x0d, 3, 0x01);
+    chunk.push(0x04);
+    chunk.push(0x04);
+    open(&mut chunk, &mut names, "EventData", false);
+    chunk.push(0x02);
+    data(&mut chunk, &mut names, "DestinationIp", 4);
+    data(&mut chunk, &mut names, "Hashes", 5);
+    data(&mut chunk, &mut names, "User", 6);
+    chunk.extend([0x04, 0x04, 0x00]);
+    let data_len: u32 = (chunk.len() - data_offset) as u32;
+    chunk[data_offset - 4..data_offset].copy_from_slice(&data_len.to_le_bytes());
+    // substitution values
+    let wide =
+        |value: &str| -> Vec<u8> { value.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+    let values: Vec<(u8, Vec<u8>)> = vec![
+        (0x06, 3u16.to_le_bytes().to_vec()),
+        (0x11, 133591178561234567u64.to_le_bytes().to_vec()),
+        (0x0a, 42u64.to_le_bytes().to_vec()),
+        (0x01, wide("WS-1.corp.local")),
+        (0x01, wide("1.2.3.4")),
+        (
+            0x01,
+            wide("MD5=D41D8CD98F00B204E9800998ECF8427E,IMPHASH=00000000000000000000000000000000"),
+        ),
+        (0x00, Vec::new()),
+    ];
+    chunk.extend((values.len() as u32).to_le_bytes());
+    for (value_type, value) in &values {
+        chunk.extend((value.len() as u16).to_le_bytes());
+        chunk.extend([*value_type, 0]);
+    }
+    for (_, value) in &values {
+        chunk.extend(value);
+    }
+    chunk.extend([0; 4]);
+    let record_len: u32 = (chunk.len() - record_offset) as u32;
+    chunk[record_offset + 4..record_offset + 8].copy_from_slice(&record_len.to_le_bytes());
+    let record_end: u32 = chunk.len() as u32;
+    chunk[48..52].copy_from_slice(&record_end.to_le_bytes());
+    chunk.resize(CHUNK_LEN, 0);
+
+    let mut evtx: Vec<u8> = b"ElfFile\0".to_vec();
+    evtx.resize(FILE_HEADER_LEN, 0);
+    evtx.extend(chunk);
+    // unused chunk
+    evtx.extend(vec![0; CHUNK_LEN]);
+
+    let mut values: Vec<(String, String)> = Vec::new();
+    read_evtx(evtx.as_slice(), |value| {
+        values.push((value.value.clone(), value.context_string()))
+    })
+    .unwrap();
+    let context = |field: &str| {
+        format!(
+            "event_id=3;computer=WS-1.corp.local;timestamp=2024-05-02T10:04:16.1234567Z;record_id=42;field={}",
+            field
+        )
+    };
+    assert_eq!(
+        values,
+        vec![
+            ("1.2.3.4".to_string(), context("DestinationIp")),
+            (
+                "D41D8CD98F00B204E9800998ECF8427E".to_string(),
+                context("md5")
+            ),
+            (
+                "00000000000000000000000000000000".to_string(),
+                context("imphash")
+            ),
+        ]
+    );
+
+    assert!(read_evtx(vec![0; FILE_HEADER_LEN].as_slice(), |_| ()).is_err());
+}
+
+#[test]
+fn test_read_evtx_expanding_template() {
+    let mut names: Vec<(String, u32)> = Vec::new();
+    let mut chunk: Vec<u8> = b"ElfChnk\0".to_vec();
+    chunk.resize(CHUNK_HEADER_LEN, 0);
+    let record_offset: usize = chunk.len();
+    chunk.extend(RECORD_SIGNATURE);
+    chunk.extend([0; 4]);
+    chunk.extend(1u64.to_le_bytes());
+    chunk.extend(0u64.to_le_bytes());
+    chunk.extend([0x0f, 1, 1, 0]);
+    chunk.extend([0x0c, 1, 0, 0, 0, 0]);
+    let definition_offset: u32 = chunk.len() as u32 + 4;
+    chunk.extend(definition_offset.to_le_bytes());
+    chunk.extend([0; 20]);
+    chunk.extend([0; 4]);
+    let data_offset: usize = chunk.len();
+    // the template uses its only substitution value four times
+    chunk.extend([0x0f, 1, 1, 0]);
+    push_named_token(
+        &mut chunk,
+        &[0x01, 0xff, 0xff, 0, 0, 0, 0],
+        "Event",
+        &mut names,
+    );
+    chunk.push(0x02);
+    for _ in 0..4 {
+        chunk.extend([0x0e, 0, 0, 0x21]);
+    }
+    chunk.extend([0x04, 0x00]);
+    let data_len: u32 = (chunk.len() - data_offset) as u32;
+    chunk[data_offset - 4..data_offset].copy_from_slice(&data_len.to_le_bytes());
+    // each value is an instance of the same template, down to the maximal depth
+    let mut value: Vec<u8> = "x".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let mut value_type: u8 = 0x01;
+    for _ in 0..MAX_DEPTH {
+        let mut instance: Vec<u8> = vec![0x0f, 1, 1, 0, 0x0c, 1, 0, 0, 0, 0];
+        instance.extend(definition_offset.to_le_bytes());
+        instance.extend(1u32.to_le_bytes());
+        instance.extend((value.len() as u16).to_le_bytes());
+        instance.extend([value_type, 0]);
+        instance.extend(value);
+        value = instance;
+        value_type = 0x21;
+    }
+    chunk.extend(1u32.to_le_bytes());
+    chunk.extend((value.len() as u16).to_le_bytes());
+    chunk.extend([value_type, 0]);
+    chunk.extend(value);
+    let record_len: u32 = (chunk.len() - record_offset) as u32;
+    chunk[record_offset + 4..record_offset + 8].copy_from_slice(&record_len.to_le_bytes());
+    let record_end: u32 = chunk.len() as u32;
+    chunk[48..52].copy_from_slice(&record_end.to_le_bytes());
+    chunk.resize(CHUNK_LEN, 0);
+
+    let mut budget: usize = MAX_EXPANDED_SIZE;
+    let mut cursor = Cursor::at(&chunk, record_offset + RECORD_HEADER_LEN);
+    assert!(parse_nodes(&mut cursor, &[], 0, &mut budget).is_none());
+    assert_eq!(budget, 0);
+
+    let mut evtx: Vec<u8> = b"ElfFile\0".to_vec();
+    evtx.resize(FILE_HEADER_LEN, 0);
+    evtx.extend(chunk);
+    assert!(read_evtx(evtx.as_slice(), |_| ()).is_ok());
+}
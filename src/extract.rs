@@ -0,0 +1,136 @@
+use regex::Regex;
+use std::collections::HashSet;
+use std::net::Ipv6Addr;
+
+// file extensions which would otherwise be taken for top level domains in filenames
+const FILE_EXTENSIONS: [&str; 24] = [
+    "bat", "bin", "cfg", "conf", "csv", "dat", "dll", "doc", "docx", "exe", "gif", "htm", "html",
+    "ini", "jpeg", "jpg", "json", "log", "pdf", "png", "ps1", "sys", "tmp", "txt",
+];
+
+pub struct IndicatorExtractor {
+    url: Regex,
+    email: Regex,
+    ipv4: Regex,
+    ipv6: Regex,
+    hash: Regex,
+    domain: Regex,
+}
+
+impl Default for IndicatorExtractor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IndicatorExtractor {
+    pub fn new() -> Self {
+        IndicatorExtractor {
+            url: Regex::new(r#"(?i)\b(?:https?|ftp)://[^\s<>"'`{}|\\^\[\]]+"#).unwrap(),
+            email: Regex::new(r"(?i)\b[a-z0-9._%+-]+@(?:[a-z0-9](?:[a-z0-9-]{0,61}[a-z0-9])?\.)+[a-z]{2,63}\b").unwrap(),
+            ipv4: Regex::new(r"\b(?:(?:25[0-5]|2[0-4][0-9]|1[0-9]{2}|[1-9]?[0-9])\.){3}(?:25[0-5]|2[0-4][0-9]|1[0-9]{2}|[1-9]?[0-9])\b").unwrap(),
+            ipv6: Regex::new(r"(?i)[0-9a-f]{0,4}(?::[0-9a-f]{0,4}){2,7}(?:(?:25[0-5]|2[0-4][0-9]|1[0-9]{2}|[1-9]?[0-9])(?:\.(?:25[0-5]|2[0-4][0-9]|1[0-9]{2}|[1-9]?[0-9])){3})?").unwrap(),
+            hash: Regex::new(r"(?i)\b(?:[0-9a-f]{64}|[0-9a-f]{40}|[0-9a-f]{32})\b").unwrap(),
+            domain: Regex::new(r"(?i)\b(?:[a-z0-9](?:[a-z0-9-]{0,61}[a-z0-9])?\.)+[a-z](?:[a-z0-9-]{0,61}[a-z0-9])?\b").unwrap(),
+        }
+    }
+
+    pub fn extract(&self, text: &str) -> Vec<String> {
+        let mut found: Vec<(usize, String)> = Vec::new();
+        // parts of URLs and emails which must not be extracted on their own
+        let mut excluded: Vec<(usize, usize)> = Vec::new();
+        for url in self.url.find_iter(text) {
+            let value: &str = url
+                .as_str()
+                .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '\'', '"']);
+            found.push((url.start(), value.to_string()));
+            let host_start: usize = url.start() + value.find("://").unwrap_or(0) + 3;
+            let host_end: usize = host_start
+                + text[host_start..url.end()]
+                    .find(['/', '?', '#'])
+                    .unwrap_or(url.end() - host_start);
+            excluded.push((url.start(), host_start));
+            excluded.push((host_end, url.end()));
+        }
+        for email in self.email.find_iter(text) {
+            found.push((email.start(), email.as_str().to_string()));
+            let at: usize = email.start() + email.as_str().find('@').unwrap_or(0);
+            excluded.push((email.start(), at + 1));
+        }
+        let is_excluded = |start: usize| {
+            excluded.iter().any(|(excluded_start, excluded_end)| {
+                start >= *excluded_start && start < *excluded_end
+            })
+        };
+        for ip in self.ipv4.find_iter(text) {
+            // skip version numbers and OIDs such as 1.2.3.4.5
+            let before: &str = &text[..ip.start()];
+            let after: &str = &text[ip.end()..];
+            let is_dotted_before = before.ends_with('.')
+                && before[..before.len() - 1].ends_with(|c: char| c.is_ascii_digit());
+            let is_dotted_after =
+                after.starts_with('.') && after[1..].starts_with(|c: char| c.is_ascii_digit());
+            if !is_dotted_before && !is_dotted_after && !is_excluded(ip.start()) {
+                found.push((ip.start(), ip.as_str().to_string()));
+            }
+        }
+        for ip in self.ipv6.find_iter(text) {
+            let is_delimited = !text[..ip.start()]
+                .ends_with(|c: char| c.is_alphanumeric() || c == ':')
+                && !text[ip.end()..].starts_with(|c: char| c.is_alphanumeric() || c == ':');
+            match ip.as_str().parse::<Ipv6Addr>() {
+                Ok(address) if is_delimited && !address.is_unspecified() => {
+                    found.push((ip.start(), ip.as_str().to_string()))
+                }
+                _ => (),
+            }
+        }
+        for hash in self.hash.find_iter(text) {
+            if !is_excluded(hash.start()) {
+                found.push((hash.start(), hash.as_str().to_string()));
+            }
+        }
+        for domain in self.domain.find_iter(text) {
+            let value: &str = domain.as_str();
+            let tld: &str = value.rsplit('.').next().unwrap_or_default();
+            if tld.len() < 2
+                || tld.chars().any(|c| c.is_ascii_digit())
+                || FILE_EXTENSIONS.contains(&tld.to_lowercase().as_str())
+                || is_excluded(domain.start())
+            {
+                continue;
+            }
+            found.push((domain.start(), value.to_string()));
+        }
+
+        found.sort_by_key(|(position, _)| *position);
+        let mut seen: HashSet<String> = HashSet::new();
+        found
+            .into_iter()
+            .filter_map(|(_, value)| {
+                if seen.insert(value.clone()) {
+                    Some(value)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_extract_indicators() {
+    let text = "Beaconing to http://evil.com/gate.php?id=1, then 10.0.0.1 and 2001:db8::1.\n\
+        Mail from bad@phish.example.org, payload d41d8cd98f00b204e9800998ecf8427e dropped as \
+        invoice.exe. Version 1.2.3.4.5 at 12:30:45 is not an IP, neither is 10.0.0.1 twice.";
+    let expected = vec![
+        "http://evil.com/gate.php?id=1",
+        "evil.com",
+        "10.0.0.1",
+        "2001:db8::1",
+        "bad@phish.example.org",
+        "phish.example.org",
+        "d41d8cd98f00b204e9800998ecf8427e",
+    ];
+    assert_eq!(IndicatorExtractor::new().extract(text), expected);
+}
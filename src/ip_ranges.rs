@@ -0,0 +1,129 @@
+use ipnet::{IpNet, Ipv4Subnets, Ipv6Subnets};
+use std::collections::HashSet;
+use std::net::IpAddr;
+
+// CIDR blocks such as 10.0.0.0/8, or ranges such as 10.0.0.1-10.0.0.9 which are converted
+// to the CIDR blocks covering them
+pub fn parse_ip_range(value: &str) -> Option<Vec<IpNet>> {
+    let value: &str = value.trim();
+    if let Ok(network) = value.parse::<IpNet>() {
+        return Some(vec![network.trunc()]);
+    }
+    let (start, end) = value.split_once('-')?;
+    match (start.trim().parse::<IpAddr>(), end.trim().parse::<IpAddr>()) {
+        (Ok(IpAddr::V4(start)), Ok(IpAddr::V4(end))) if start <= end => {
+            Some(Ipv4Subnets::new(start, end, 0).map(IpNet::V4).collect())
+        }
+        (Ok(IpAddr::V6(start)), Ok(IpAddr::V6(end))) if start <= end => {
+            Some(Ipv6Subnets::new(start, end, 0).map(IpNet::V6).collect())
+        }
+        _ => None,
+    }
+}
+
+pub fn split_ip_ranges(values: Vec<String>) -> (Vec<String>, Vec<IpNet>) {
+    let mut other_values: Vec<String> = Vec::new();
+    let mut ranges: Vec<IpNet> = Vec::new();
+    for value in values {
+        match parse_ip_range(&value) {
+            Some(networks) => ranges.extend(networks),
+            None => other_values.push(value),
+        }
+    }
+    ranges.sort();
+    ranges.dedup();
+    (other_values, ranges)
+}
+
+// networks grouped by prefix length, a network is looked up once for each prefix length
+pub struct IpRangeIndex {
+    networks: HashSet<IpNet>,
+    ipv4_prefix_lens: Vec<u8>,
+    ipv6_prefix_lens: Vec<u8>,
+}
+
+impl IpRangeIndex {
+    pub fn new(ranges: &[IpNet]) -> Self {
+        let mut ipv4_prefix_lens: Vec<u8> = Vec::new();
+        let mut ipv6_prefix_lens: Vec<u8> = Vec::new();
+        for range in ranges {
+            match range {
+                IpNet::V4(_) => ipv4_prefix_lens.push(range.prefix_len()),
+                IpNet::V6(_) => ipv6_prefix_lens.push(range.prefix_len()),
+            }
+        }
+        for prefix_lens in [&mut ipv4_prefix_lens, &mut ipv6_prefix_lens] {
+            prefix_lens.sort_by(|a, b| b.cmp(a));
+            prefix_lens.dedup();
+        }
+        IpRangeIndex {
+            networks: ranges.iter().map(|range| range.trunc()).collect(),
+            ipv4_prefix_lens,
+            ipv6_prefix_lens,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty()
+    }
+
+    // the most specific range containing the network, IPs being networks of a single address
+    pub fn find(&self, network: &IpNet) -> Option<IpNet> {
+        let prefix_lens: &Vec<u8> = match network {
+            IpNet::V4(_) => &self.ipv4_prefix_lens,
+            IpNet::V6(_) => &self.ipv6_prefix_lens,
+        };
+        prefix_lens
+            .iter()
+            .filter(|prefix_len| **prefix_len <= network.prefix_len())
+            .filter_map(|prefix_len| IpNet::new(network.network(), *prefix_len).ok())
+            .map(|range| range.trunc())
+            .find(|range| self.networks.contains(range))
+    }
+}
+
+#[test]
+fn test_parse_ip_range() {
+    assert_eq!(
+        parse_ip_range("10.1.2.3/8"),
+        Some(vec!["10.0.0.0/8".parse().unwrap()])
+    );
+    assert_eq!(
+        parse_ip_range("10.0.0.0-10.0.1.255"),
+        Some(vec!["10.0.0.0/23".parse().unwrap()])
+    );
+    assert_eq!(
+        parse_ip_range("10.0.0.1 - 10.0.0.2"),
+        Some(vec![
+            "10.0.0.1/32".parse().unwrap(),
+            "10.0.0.2/32".parse().unwrap()
+        ])
+    );
+    assert_eq!(
+        parse_ip_range("2001:db8::/32"),
+        Some(vec!["2001:db8::/32".parse().unwrap()])
+    );
+    assert_eq!(parse_ip_range("10.0.0.9-10.0.0.1"), None);
+    assert_eq!(parse_ip_range("10.0.0.1"), None);
+    assert_eq!(parse_ip_range("evil.com"), None);
+}
+
+#[test]
+fn test_ip_range_index() {
+    let ranges: Vec<IpNet> = vec![
+        "10.0.0.0/8".parse().unwrap(),
+        "10.1.0.0/16".parse().unwrap(),
+        "2001:db8::/32".parse().unwrap(),
+    ];
+    let index = IpRangeIndex::new(&ranges);
+    let find = |value: &str| index.find(&value.parse().unwrap());
+    assert_eq!(find("10.1.2.3/32"), Some("10.1.0.0/16".parse().unwrap()));
+    assert_eq!(find("10.2.2.3/32"), Some("10.0.0.0/8".parse().unwrap()));
+    assert_eq!(find("10.1.128.0/17"), Some("10.1.0.0/16".parse().unwrap()));
+    assert_eq!(find("10.0.0.0/7"), None);
+    assert_eq!(find("11.0.0.1/32"), None);
+    assert_eq!(
+        find("2001:db8::1/128"),
+        Some("2001:db8::/32".parse().unwrap())
+    );
+}
@@ -0,0 +1,177 @@
+use crate::InputValue;
+use serde_json::Value;
+use std::io::{self, BufRead};
+
+// path to fields of JSON records, such as /process/hashes/sha256, arrays being traversed
+// unless the segment is an index
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonPath {
+    path: String,
+    segments: Vec<String>,
+}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<Self, String> {
+        let segments: Vec<String> = path
+            .strip_prefix('/')
+            .unwrap_or(path)
+            .split('/')
+            .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+            .collect();
+        if segments.iter().any(String::is_empty) {
+            return Err(format!("Invalid field path {}", path));
+        }
+        Ok(JsonPath {
+            path: path.to_string(),
+            segments,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.path
+    }
+
+    pub fn select<'a>(&self, record: &'a Value) -> Vec<&'a Value> {
+        let mut selected: Vec<&'a Value> = vec![record];
+        for segment in &self.segments {
+            selected = selected
+                .into_iter()
+                .flat_map(|value| select_segment(value, segment))
+                .collect();
+        }
+        // the values of arrays selected as a whole
+        selected
+            .into_iter()
+            .flat_map(|value| match value {
+                Value::Array(values) => values.iter().collect(),
+                value => vec![value],
+            })
+            .collect()
+    }
+}
+
+fn select_segment<'a>(value: &'a Value, segment: &str) -> Vec<&'a Value> {
+    match value {
+        Value::Object(object) => object.get(segment).into_iter().collect(),
+        Value::Array(values) => match segment.parse::<usize>() {
+            Ok(index) => values.get(index).into_iter().collect(),
+            Err(_) => values
+                .iter()
+                .flat_map(|value| select_segment(value, segment))
+                .collect(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+// reads a JSON Lines file, checking the values of the fields selected in each record
+pub fn read_jsonl<F: FnMut(InputValue)>(
+    mut reader: impl BufRead,
+    fields: &[JsonPath],
+    context_fields: &[JsonPath],
+    mut callback: F,
+) -> Result<(), io::Error> {
+    if fields.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "At least one field to read is required for JSON Lines input",
+        ));
+    }
+    let mut line: String = String::new();
+    let mut line_number: usize = 0;
+    while reader.read_line(&mut line)? > 0 {
+        line_number += 1;
+        if !line.trim().is_empty() {
+            let record: Value = serde_json::from_str(&line).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", line_number, e),
+                )
+            })?;
+            let mut context: Vec<(String, String)> =
+                vec![("line".to_string(), line_number.to_string())];
+            for context_field in context_fields {
+                let values: Vec<String> = context_field
+                    .select(&record)
+                    .into_iter()
+                    .filter_map(value_to_string)
+                    .collect();
+                if !values.is_empty() {
+                    context.push((context_field.as_str().to_string(), values.join(",")));
+                }
+            }
+            for field in fields {
+                for value in field.select(&record).into_iter().filter_map(Value::as_str) {
+                    let value: &str = value.trim();
+                    if value.is_empty() {
+                        continue;
+                    }
+                    let mut context: Vec<(String, String)> = context.clone();
+                    context.push(("field".to_string(), field.as_str().to_string()));
+                    callback(InputValue::with_context(value.to_string(), context));
+                }
+            }
+        }
+        line.clear();
+    }
+    Ok(())
+}
+
+#[test]
+fn test_json_path() {
+    let record: Value = serde_json::from_str(
+        r#"{"host":{"name":"ws-1"},"dns":{"answers":[{"data":"1.2.3.4"},{"data":"5.6.7.8"}]},"tags":["a","b"],"a/b":"c"}"#,
+    )
+    .unwrap();
+    let select = |path: &str| JsonPath::parse(path).unwrap().select(&record);
+    assert_eq!(select("/host/name"), vec!["ws-1"]);
+    assert_eq!(select("host/name"), vec!["ws-1"]);
+    assert_eq!(select("/dns/answers/data"), vec!["1.2.3.4", "5.6.7.8"]);
+    assert_eq!(select("/dns/answers/1/data"), vec!["5.6.7.8"]);
+    assert_eq!(select("/tags"), vec!["a", "b"]);
+    assert_eq!(select("/a~1b"), vec!["c"]);
+    assert!(select("/host/missing").is_empty());
+    assert!(JsonPath::parse("/host//name").is_err());
+}
+
+#[test]
+fn test_read_jsonl() {
+    let jsonl: &str = "{\"user\":\"alice\",\"event\":{\"id\":7},\"urls\":[\"http://evil.com/a\",\"http://bad.com\"]}\n\
+        \n\
+        {\"user\":\"bob\",\"urls\":[]}\n";
+    let mut values: Vec<(String, String)> = Vec::new();
+    read_jsonl(
+        jsonl.as_bytes(),
+        &[JsonPath::parse("/urls").unwrap()],
+        &[
+            JsonPath::parse("/user").unwrap(),
+            JsonPath::parse("/event/id").unwrap(),
+        ],
+        |value| values.push((value.value.clone(), value.context_string())),
+    )
+    .unwrap();
+    assert_eq!(
+        values,
+        vec![
+            (
+                "http://evil.com/a".to_string(),
+                "line=1;/user=alice;/event/id=7;field=/urls".to_string()
+            ),
+            (
+                "http://bad.com".to_string(),
+                "line=1;/user=alice;/event/id=7;field=/urls".to_string()
+            ),
+        ]
+    );
+
+    assert!(read_jsonl("{}".as_bytes(), &[], &[], |_| ()).is_err());
+}
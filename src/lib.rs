@@ -1,13 +1,58 @@
+use access_log::{read_access_log, read_squid_log, LogFormat, COMBINED_LOG_FORMAT};
+use atom_type::{detect_atom_type, AtomType};
 use bloomfilter::Bloom;
-use csv::{Reader, ReaderBuilder, Writer};
+use canonical::{canonicalize, CANONICALIZATION_VERSION};
+use compression::decompress_reader;
+use csv::{Reader, ReaderBuilder, StringRecord, Writer};
+use defang::{defang, refang};
+use dns_log::{read_bind_log, read_dnsmasq_log, read_unbound_log, read_windows_dns_log};
+use email::read_email;
+use encoding::DecodingReader;
+use encoding_rs::Encoding;
+use evtx::read_evtx;
+use extract::IndicatorExtractor;
+use ip_ranges::{parse_ip_range, split_ip_ranges, IpRangeIndex};
+use ipnet::IpNet;
+use jsonl::{read_jsonl, JsonPath};
 use ocd_datalake_rs::error::DatalakeError;
 use ocd_datalake_rs::{Datalake, DatalakeSetting};
+use pcap::read_pcap;
+use public_suffix::parent_domains;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use spinners::{Spinner, Spinners};
-use std::collections::{HashMap, HashSet};
+use spreadsheet::{read_spreadsheet, SpreadsheetFormat};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::fs::File;
 use std::io::{self, prelude::*};
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use suricata::read_eve_log;
+use url_components::{decompose_url, UrlComponents};
+use zeek::read_zeek_log;
+
+pub mod access_log;
+pub mod atom_type;
+pub mod bundle;
+pub mod canonical;
+pub mod compression;
+pub mod defang;
+pub mod dns_log;
+pub mod email;
+pub mod encoding;
+pub mod evtx;
+pub mod extract;
+pub mod ip_ranges;
+pub mod jsonl;
+pub mod patch;
+pub mod pcap;
+pub mod public_suffix;
+pub mod spreadsheet;
+pub mod suricata;
+pub mod url_components;
+pub mod zeek;
 
 pub fn get_filename_from_path(path: &Path) -> Result<String, String> {
     match path.file_name().and_then(|name| name.to_str()) {
@@ -16,55 +61,443 @@ pub fn get_filename_from_path(path: &Path) -> Result<String, String> {
     }
 }
 
-pub fn read_input_file(path: &PathBuf) -> Result<Vec<String>, io::Error> {
-    let mut reader = ReaderBuilder::new().has_headers(false).from_path(path)?;
-    let mut input: Vec<String> = Vec::new();
-    for result in reader.records() {
-        let record = result?;
-        let atom: String = match record.get(0) {
-            Some(atom) => atom.trim().to_string(),
+pub const STDIN_PATH: &str = "-";
+
+// gzip, zstd, bzip2 and xz inputs are decompressed while they are read
+pub fn open_input(path: &Path) -> Result<Box<dyn BufRead>, io::Error> {
+    if path.as_os_str() == STDIN_PATH {
+        return decompress_reader(Box::new(io::stdin().lock()));
+    }
+    decompress_reader(Box::new(io::BufReader::new(File::open(path)?)))
+}
+
+pub fn read_input_file<F: FnMut(InputValue)>(
+    reader: impl Read,
+    options: &InputOptions,
+    mut callback: F,
+) -> Result<(), io::Error> {
+    let mut records = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(options.delimiter)
+        .comment(options.comment)
+        .from_reader(reader)
+        .into_records();
+    let header: Option<StringRecord> = if has_header(&options.columns, options.header) {
+        records.next().transpose()?
+    } else {
+        None
+    };
+    let columns: Vec<(usize, String)> = resolve_columns(&options.columns, header.as_ref())?;
+
+    for record in records {
+        let record: StringRecord = record?;
+        if columns.is_empty() {
+            match record.get(0).map(str::trim) {
+                Some(atom) if !atom.is_empty() => callback(InputValue::new(refang(atom))),
+                _ => (),
+            }
+            continue;
+        }
+        for (index, name) in &columns {
+            match record.get(*index).map(str::trim) {
+                Some(atom) if !atom.is_empty() => callback(InputValue::with_context(
+                    refang(atom),
+                    vec![("column".to_string(), name.clone())],
+                )),
+                _ => (),
+            }
+        }
+    }
+    Ok(())
+}
+
+// indexes and names of the selected columns
+fn resolve_columns(
+    columns: &[String],
+    header: Option<&StringRecord>,
+) -> Result<Vec<(usize, String)>, io::Error> {
+    let mut resolved: Vec<(usize, String)> = Vec::new();
+    for column in columns {
+        let index: Option<usize> = match header {
+            Some(header) => header
+                .iter()
+                .position(|name| name.trim() == column)
+                .or_else(|| column.parse().ok()),
+            None => column.parse().ok(),
+        };
+        let index: usize = match index {
+            Some(index) => index,
             None => {
                 return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("{}: No data found in file", path.display()),
+                    io::ErrorKind::InvalidInput,
+                    format!("No column named {}", column),
                 ))
             }
         };
-        input.push(atom);
+        let name: String = header
+            .and_then(|header| header.get(index))
+            .map_or(index.to_string(), |name| name.trim().to_string());
+        resolved.push((index, name));
+    }
+    Ok(resolved)
+}
+
+// the first row is only taken as a header when asked to or when a column is selected by name,
+// guessing it could drop a value without notice
+pub(crate) fn has_header(columns: &[String], header: bool) -> bool {
+    header
+        || columns
+            .iter()
+            .any(|column| column.parse::<usize>().is_err())
+}
+
+// indicators are extracted line by line so that the input doesn't need to fit in memory
+fn extract_input_values<F: FnMut(InputValue)>(
+    mut reader: impl BufRead,
+    mut callback: F,
+) -> Result<(), io::Error> {
+    let extractor = IndicatorExtractor::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut line: Vec<u8> = Vec::new();
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        let text: String = refang(&String::from_utf8_lossy(&line));
+        for value in extractor.extract(&text) {
+            if seen.insert(value.clone()) {
+                callback(InputValue::new(value));
+            }
+        }
+        line.clear();
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum InputFormat {
+    #[default]
+    Csv,
+    Zeek,
+    Suricata,
+    Jsonl,
+    Pcap,
+    Evtx,
+    Squid,
+    Combined,
+    AccessLog,
+    Bind,
+    Dnsmasq,
+    Unbound,
+    WindowsDns,
+    Eml,
+    Xlsx,
+    Ods,
+}
+
+impl InputFormat {
+    // binary formats and emails, which declare their own charsets, are read without decoding
+    pub fn is_text(&self) -> bool {
+        !matches!(
+            self,
+            InputFormat::Pcap
+                | InputFormat::Evtx
+                | InputFormat::Eml
+                | InputFormat::Xlsx
+                | InputFormat::Ods
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct InputOptions {
+    pub extract: bool,
+    pub format: InputFormat,
+    // names or indexes of the CSV or spreadsheet columns to read, the first column is read if empty
+    pub columns: Vec<String>,
+    // names or indexes of the sheets of spreadsheets to read, all the sheets are read if empty
+    pub sheets: Vec<String>,
+    pub delimiter: u8,
+    // whether the first row of CSV files and spreadsheets is a header, which it also is when a
+    // column is selected by name
+    pub header: bool,
+    // lines starting with this character are skipped, none are by default
+    pub comment: Option<u8>,
+    // fields of the JSON Lines records to read, and to show as context of their values
+    pub fields: Vec<JsonPath>,
+    pub context_fields: Vec<JsonPath>,
+    // format of the lines of access logs
+    pub log_format: Option<LogFormat>,
+    // encoding of text input, detected from its BOM or UTF-8 if not given
+    pub encoding: Option<&'static Encoding>,
+}
+
+impl Default for InputOptions {
+    fn default() -> Self {
+        InputOptions {
+            extract: false,
+            format: InputFormat::Csv,
+            columns: Vec::new(),
+            sheets: Vec::new(),
+            delimiter: b',',
+            header: false,
+            comment: None,
+            fields: Vec::new(),
+            context_fields: Vec::new(),
+            log_format: None,
+            encoding: None,
+        }
     }
+}
+
+pub fn read_input(path: &Path, options: &InputOptions) -> Result<Vec<String>, io::Error> {
+    Ok(read_input_values(path, options)?
+        .into_iter()
+        .map(|input_value| input_value.value)
+        .collect())
+}
+
+pub fn read_input_values(
+    path: &Path,
+    options: &InputOptions,
+) -> Result<Vec<InputValue>, io::Error> {
+    let mut input: Vec<InputValue> = Vec::new();
+    for_each_input_value(path, options, |input_value| input.push(input_value))?;
     Ok(input)
 }
 
-pub fn write_csv(
-    matches: &HashMap<String, Vec<String>>,
-    output: &PathBuf,
-    no_header: &bool,
-) -> Result<(), String> {
-    let mut writer: Writer<File> = match Writer::from_path(output) {
-        Ok(writer) => writer,
-        Err(e) => return Err(format!("{}: {}", &output.display(), e)),
-    };
-    if !no_header {
-        match writer.write_record(["matching_value", "bloom_filename"]) {
-            // write the csv header
-            Ok(()) => (),
-            Err(e) => return Err(format!("{}: {}", &output.display(), e)),
+// reads the input file, or stdin for `-`, calling the callback on each value as it is read,
+// and returns the numbers of the lines in which undecodable bytes were replaced with U+FFFD,
+// the values containing them being dropped rather than checked
+pub fn for_each_input_value<F: FnMut(InputValue)>(
+    path: &Path,
+    options: &InputOptions,
+    mut callback: F,
+) -> Result<Vec<usize>, io::Error> {
+    let reader: Box<dyn BufRead> = open_input(path)?;
+    if !options.extract && !options.format.is_text() {
+        read_input_values_from(reader, options, callback)?;
+        return Ok(Vec::new());
+    }
+    let mut decoding_reader = DecodingReader::new(reader, options.encoding);
+    read_input_values_from(
+        Box::new(io::BufReader::new(&mut decoding_reader)),
+        options,
+        |input_value| {
+            if !input_value.value.contains(char::REPLACEMENT_CHARACTER) {
+                callback(input_value)
+            }
+        },
+    )?;
+    Ok(decoding_reader.replaced_lines().to_vec())
+}
+
+fn read_input_values_from<F: FnMut(InputValue)>(
+    reader: Box<dyn BufRead + '_>,
+    options: &InputOptions,
+    callback: F,
+) -> Result<(), io::Error> {
+    if options.extract {
+        return extract_input_values(reader, callback);
+    }
+    match options.format {
+        InputFormat::Csv => read_input_file(reader, options, callback),
+        InputFormat::Zeek => read_zeek_log(reader, callback),
+        InputFormat::Suricata => read_eve_log(reader, callback),
+        InputFormat::Jsonl => {
+            read_jsonl(reader, &options.fields, &options.context_fields, callback)
+        }
+        InputFormat::Pcap => read_pcap(reader, callback),
+        InputFormat::Evtx => read_evtx(reader, callback),
+        InputFormat::Squid => read_squid_log(reader, callback),
+        InputFormat::Combined => match LogFormat::parse(COMBINED_LOG_FORMAT) {
+            Ok(format) => read_access_log(reader, &format, callback),
+            Err(e) => Err(io::Error::new(io::ErrorKind::InvalidInput, e)),
+        },
+        InputFormat::AccessLog => match &options.log_format {
+            Some(format) => read_access_log(reader, format, callback),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "A log format is required to read access logs",
+            )),
+        },
+        InputFormat::Bind => read_bind_log(reader, callback),
+        InputFormat::Dnsmasq => read_dnsmasq_log(reader, callback),
+        InputFormat::Unbound => read_unbound_log(reader, callback),
+        InputFormat::WindowsDns => read_windows_dns_log(reader, callback),
+        InputFormat::Eml => read_email(reader, callback),
+        InputFormat::Xlsx => read_spreadsheet(
+            reader,
+            SpreadsheetFormat::Xlsx,
+            &options.sheets,
+            &options.columns,
+            has_header(&options.columns, options.header),
+            callback,
+        ),
+        InputFormat::Ods => read_spreadsheet(
+            reader,
+            SpreadsheetFormat::Ods,
+            &options.sheets,
+            &options.columns,
+            has_header(&options.columns, options.header),
+            callback,
+        ),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputValue {
+    pub value: String,
+    pub atom_type: AtomType,
+    // where the value comes from in the input, such as its column
+    pub context: Vec<(String, String)>,
+}
+
+impl InputValue {
+    pub fn new(value: String) -> Self {
+        InputValue::with_context(value, Vec::new())
+    }
+
+    pub fn with_context(value: String, context: Vec<(String, String)>) -> Self {
+        let atom_type: AtomType = detect_atom_type(&value);
+        InputValue {
+            value,
+            atom_type,
+            context,
+        }
+    }
+
+    pub fn context_string(&self) -> String {
+        self.context
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<String>>()
+            .join(";")
+    }
+}
+
+pub fn classify_values(values: Vec<String>) -> Vec<InputValue> {
+    values.into_iter().map(InputValue::new).collect()
+}
+
+pub fn get_atom_types(values: &[InputValue]) -> Vec<AtomType> {
+    let mut atom_types: Vec<AtomType> = values.iter().map(|value| value.atom_type).collect();
+    atom_types.sort();
+    atom_types.dedup();
+    atom_types
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BloomMatch {
+    pub input: InputValue,
+    // value found in the bloom filter, which differs from the input value for parent domains,
+    // URL components and IP ranges
+    pub matched_value: String,
+    pub matched_component: MatchedComponent,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchedComponent {
+    Value,
+    ParentDomain,
+    Host,
+    RegisteredDomain,
+    Ip,
+    Range,
+}
+
+impl MatchedComponent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MatchedComponent::Value => "value",
+            MatchedComponent::ParentDomain => "parent_domain",
+            MatchedComponent::Host => "host",
+            MatchedComponent::RegisteredDomain => "registered_domain",
+            MatchedComponent::Ip => "ip",
+            MatchedComponent::Range => "range",
+        }
+    }
+}
+
+pub const MATCH_HEADER: [&str; 7] = [
+    "matching_value",
+    "atom_type",
+    "matched_value",
+    "matched_component",
+    "bloom_filename",
+    "bloom_age_days",
+    "context",
+];
+
+impl BloomMatch {
+    // fields of the match in the output, in the order of MATCH_HEADER
+    pub fn to_record(
+        &self,
+        bloom_filename: &str,
+        bloom_age: &str,
+        defang_values: bool,
+    ) -> Vec<String> {
+        let (value, matched_value): (String, String) = if defang_values {
+            (defang(&self.input.value), defang(&self.matched_value))
+        } else {
+            (self.input.value.clone(), self.matched_value.clone())
         };
+        vec![
+            value,
+            self.input.atom_type.to_string(),
+            matched_value,
+            self.matched_component.as_str().to_string(),
+            bloom_filename.to_string(),
+            bloom_age.to_string(),
+            self.input.context_string(),
+        ]
     }
-    for (filename, values) in matches {
-        for val in values {
-            match writer.write_record([val, filename]) {
-                Ok(()) => (),
+}
+
+// matches are written as they are found, so the output file is only created with the first one
+pub struct MatchWriter<W: Write = File> {
+    writer: Writer<W>,
+    path: PathBuf,
+}
+
+impl MatchWriter<io::Stdout> {
+    // matches printed on stdout are quoted as in the output file, without header
+    pub fn stdout() -> Self {
+        MatchWriter {
+            writer: Writer::from_writer(io::stdout()),
+            path: PathBuf::from("stdout"),
+        }
+    }
+}
+
+impl MatchWriter<File> {
+    pub fn create(output: &PathBuf, no_header: bool) -> Result<Self, String> {
+        let mut match_writer = MatchWriter {
+            writer: match Writer::from_path(output) {
+                Ok(writer) => writer,
                 Err(e) => return Err(format!("{}: {}", &output.display(), e)),
-            }
+            },
+            path: output.clone(),
+        };
+        if !no_header {
+            match_writer.write(&MATCH_HEADER)?;
+        }
+        Ok(match_writer)
+    }
+}
+
+impl<W: Write> MatchWriter<W> {
+    pub fn write<T: AsRef<[u8]>>(&mut self, record: &[T]) -> Result<(), String> {
+        match self.writer.write_record(record) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("{}: {}", &self.path.display(), e)),
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<(), String> {
+        match self.writer.flush() {
+            Ok(()) => Ok(()),
+            Err(e) => Err(format!("{}: {}", &self.path.display(), e)),
         }
     }
-    match writer.flush() {
-        // flush the internal buffer
-        Ok(()) => (),
-        Err(e) => return Err(format!("{}: {}", &output.display(), e)),
-    };
-    Ok(())
 }
 
 pub fn write_file(output_path: &PathBuf, content: String) -> Result<(), String> {
@@ -79,17 +512,101 @@ pub fn write_file(output_path: &PathBuf, content: String) -> Result<(), String>
     Ok(())
 }
 
-pub fn write_bloom_to_file(bloom: &Bloom<String>, output_path: &PathBuf) -> Result<(), String> {
-    let serialized_bloom: String = serialize_bloom(bloom)?;
+pub const SECONDS_PER_DAY: u64 = 86400;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct BloomMetadata {
+    #[serde(default)]
+    pub created_at: Option<u64>,
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    #[serde(default)]
+    pub canonicalization: Option<u32>,
+    // types of the values the bloom filter was created from, unknown for older bloom filters
+    #[serde(default)]
+    pub atom_types: Option<Vec<AtomType>>,
+    // false positive rate requested when the bloom filter was created
+    #[serde(default)]
+    pub rate: Option<f64>,
+}
+
+impl BloomMetadata {
+    pub fn new(expires_in_days: Option<u64>) -> Self {
+        let created_at: u64 = now_timestamp();
+        BloomMetadata {
+            created_at: Some(created_at),
+            canonicalization: Some(CANONICALIZATION_VERSION),
+            expires_at: expires_in_days.map(|days| created_at + days * SECONDS_PER_DAY),
+            atom_types: None,
+            rate: None,
+        }
+    }
+
+    pub fn accepts(&self, atom_type: AtomType) -> bool {
+        match &self.atom_types {
+            Some(atom_types) => atom_types.contains(&atom_type),
+            None => true,
+        }
+    }
+
+    pub fn age_in_days(&self, now: u64) -> Option<u64> {
+        self.created_at
+            .map(|created_at| now.saturating_sub(created_at) / SECONDS_PER_DAY)
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        match self.expires_at {
+            Some(expires_at) => now >= expires_at,
+            None => false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BloomFile {
+    pub metadata: BloomMetadata,
+    pub bloom: Bloom<String>,
+    // CIDR blocks can't be stored in the bloom filter as they match many IPs
+    #[serde(default)]
+    pub ranges: Vec<IpNet>,
+}
+
+impl BloomFile {
+    pub fn new(bloom: Bloom<String>, expires_in_days: Option<u64>) -> Self {
+        BloomFile {
+            metadata: BloomMetadata::new(expires_in_days),
+            bloom,
+            ranges: Vec::new(),
+        }
+    }
+}
+
+pub fn now_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn write_bloom_to_file(bloom_file: &BloomFile, output_path: &PathBuf) -> Result<(), String> {
+    let serialized_bloom: String = serialize_bloom_file(bloom_file)?;
     write_file(output_path, serialized_bloom)
 }
 
 pub fn deserialize_bloom(path: &PathBuf) -> Result<Bloom<String>, String> {
+    Ok(deserialize_bloom_file(path)?.bloom)
+}
+
+pub fn deserialize_bloom_file(path: &PathBuf) -> Result<BloomFile, String> {
     let ron_string: String = match std::fs::read_to_string(path) {
         Ok(ron_string) => ron_string,
         Err(e) => return Err(format!("{}: {}", path.display(), e)),
     };
 
+    if let Ok(bloom_file) = ron::from_str::<BloomFile>(&ron_string) {
+        return Ok(bloom_file);
+    }
+    // bloom filters created before metadata was added only contain the filter itself
     let bloom: Bloom<String> = match ron::from_str(&ron_string) {
         Ok(bloom) => bloom,
         Err(_) => {
@@ -99,7 +616,11 @@ pub fn deserialize_bloom(path: &PathBuf) -> Result<Bloom<String>, String> {
             ))
         }
     };
-    Ok(bloom)
+    Ok(BloomFile {
+        metadata: BloomMetadata::default(),
+        bloom,
+        ranges: Vec::new(),
+    })
 }
 
 pub fn serialize_bloom(bloom: &Bloom<String>) -> Result<String, String> {
@@ -107,37 +628,78 @@ pub fn serialize_bloom(bloom: &Bloom<String>) -> Result<String, String> {
     Ok(serialized)
 }
 
+pub fn serialize_bloom_file(bloom_file: &BloomFile) -> Result<String, String> {
+    let serialized: String =
+        ron::to_string(&bloom_file).expect("Failed to serialize the bloomfilter");
+    Ok(serialized)
+}
+
 pub fn create_bloom(input: Vec<String>, size: usize, positive_rate: f64) -> Bloom<String> {
+    create_bloom_with_seed(input, size, positive_rate, None)
+}
+
+pub fn create_bloom_with_seed(
+    input: Vec<String>,
+    size: usize,
+    positive_rate: f64,
+    seed: Option<&[u8; 32]>,
+) -> Bloom<String> {
     let mut spinner = Spinner::with_timer(Spinners::Line, "Creating bloom filter".to_string());
-    let mut bloom: Bloom<String> = Bloom::new_for_fp_rate(size, positive_rate);
+    let mut bloom: Bloom<String> = match seed {
+        Some(seed) => Bloom::new_for_fp_rate_with_seed(size, positive_rate, seed),
+        None => Bloom::new_for_fp_rate(size, positive_rate),
+    };
     for value in input {
-        bloom.set(&value);
+        bloom.set(&canonicalize(&value));
     }
     spinner.stop_and_persist("✔", "Finished creating the Bloom filter.".into());
     bloom
 }
 
-pub fn create_bloom_from_file(
-    input_path: &PathBuf,
-    positive_rate: f64,
-) -> Result<Bloom<String>, String> {
+pub fn seed_from_passphrase(passphrase: &str) -> [u8; 32] {
+    Sha256::digest(passphrase.as_bytes()).into()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn bloom_checksum(bloom: &Bloom<String>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(bloom.number_of_bits().to_le_bytes());
+    hasher.update(bloom.number_of_hash_functions().to_le_bytes());
+    for (key0, key1) in bloom.sip_keys() {
+        hasher.update(key0.to_le_bytes());
+        hasher.update(key1.to_le_bytes());
+    }
+    hasher.update(bloom.bitmap());
+    to_hex(&hasher.finalize())
+}
+
+// returns the values of the file, and the numbers of the lines with undecodable bytes
+pub fn get_values_from_file(
+    input_path: &Path,
+    options: &InputOptions,
+) -> Result<(Vec<String>, Vec<usize>), String> {
     let mut spinner = Spinner::with_timer(Spinners::Line, "Reading input file...".to_string());
-    let input: Vec<String> = match read_input_file(input_path) {
-        Ok(input) => {
+    let mut input: Vec<String> = Vec::new();
+    let read_result = for_each_input_value(input_path, options, |input_value| {
+        input.push(input_value.value)
+    });
+    let replaced_lines: Vec<usize> = match read_result {
+        Ok(replaced_lines) => {
             spinner.stop_and_persist("✔", "Successfully extracted data from file.".into());
-            input
+            replaced_lines
         }
         Err(e) => {
             spinner.stop_and_persist("✗", "Failed.".into());
             return Err(format!("{}: {}", input_path.display(), e));
         }
     };
-    let size: usize = input.len();
-    if size == 0 {
+    if input.is_empty() {
         return Err(format!("{}: No data found in file", input_path.display()));
     }
-    let bloom: Bloom<String> = create_bloom(input, size, positive_rate);
-    Ok(bloom)
+    Ok((input, replaced_lines))
 }
 
 pub fn create_bloom_from_queryhash(
@@ -145,6 +707,16 @@ pub fn create_bloom_from_queryhash(
     environment: &String,
     positive_rate: f64,
 ) -> Result<Bloom<String>, String> {
+    let atom_values: Vec<String> = get_values_from_queryhash(query_hash, environment)?;
+    let size: usize = atom_values.len();
+    let bloom: Bloom<String> = create_bloom(atom_values, size, positive_rate);
+    Ok(bloom)
+}
+
+pub fn get_values_from_queryhash(
+    query_hash: String,
+    environment: &String,
+) -> Result<Vec<String>, String> {
     let dtl: Datalake = match init_datalake(environment) {
         Ok(dtl) => dtl,
         Err(e) => return Err(format!("{}", e)),
@@ -165,12 +737,10 @@ pub fn create_bloom_from_queryhash(
         }
     };
 
-    let size: usize = atom_values.len();
-    if size == 0 {
+    if atom_values.is_empty() {
         return Err("No data found in Datalake!".into());
     }
-    let bloom: Bloom<String> = create_bloom(atom_values, size, positive_rate);
-    Ok(bloom)
+    Ok(atom_values)
 }
 
 fn fetch_atom_values_from_dtl(query_hash: String, mut dtl: Datalake) -> Result<String, String> {
@@ -203,7 +773,7 @@ fn fetch_atom_values_from_dtl(query_hash: String, mut dtl: Datalake) -> Result<S
             sp.stop_and_persist("✗", "Failed.".into());
             match e {
                 DatalakeError::ApiError(detailled_error) => {
-                    let api_resp = match { detailled_error.api_response } {
+                    let api_resp = match detailled_error.api_response {
                         Some(resp) => resp,
                         None => "API responded without a message.".to_string(),
                     };
@@ -254,12 +824,19 @@ fn init_datalake(environment: &String) -> Result<Datalake, io::Error> {
     Ok(Datalake::new(username, password, long_term_token, dtl_setting).unwrap())
 }
 
+// whether Datalake can be logged in to without prompting for credentials on stdin
+pub fn has_datalake_credentials() -> bool {
+    let is_set = |name: &str| env::var(name).is_ok_and(|value| !value.is_empty());
+    is_set("OCD_DTL_RS_LONG_TERM_TOKEN")
+        || (is_set("OCD_DTL_RS_USERNAME") && is_set("OCD_DTL_RS_PASSWORD"))
+}
+
 fn get_long_term_token() -> Result<String, io::Error> {
     match env::var("OCD_DTL_RS_LONG_TERM_TOKEN") {
         Ok(long_term_token) => Ok(long_term_token),
         Err(_) => {
             println!("Set the environment variable OCD_DTL_RS_LONG_TERM_TOKEN to avoid entering your long-term token each time.");
-            println!("Enter your long-term token (leave empty to log in with username/password):");            
+            println!("Enter your long-term token (leave empty to log in with username/password):");
             let mut long_term_token = String::new();
             match io::stdin().read_line(&mut long_term_token) {
                 Ok(_) => (),
@@ -316,14 +893,125 @@ fn get_password() -> Result<String, io::Error> {
     }
 }
 
-pub fn get_bloom_from_paths(
+pub const CATALOG_FILENAME: &str = "catalog.ron";
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CatalogEntry {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Catalog {
+    pub filters: Vec<CatalogEntry>,
+}
+
+pub fn read_catalog(path: &PathBuf) -> Result<Catalog, String> {
+    let ron_string: String = match std::fs::read_to_string(path) {
+        Ok(ron_string) => ron_string,
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    match ron::from_str(&ron_string) {
+        Ok(catalog) => Ok(catalog),
+        Err(e) => Err(format!("{}: Failed to read catalog: {}", path.display(), e)),
+    }
+}
+
+pub fn resolve_bloom_paths(
     bloom_paths: &Vec<PathBuf>,
-) -> Result<HashMap<String, Bloom<String>>, String> {
-    let mut blooms: HashMap<String, Bloom<String>> = HashMap::new();
+    tags: &[String],
+) -> Result<Vec<(String, PathBuf)>, String> {
+    let mut resolved: Vec<(String, PathBuf)> = Vec::new();
     for path in bloom_paths {
-        let filename = get_filename_from_path(path)?;
-        let bloom = deserialize_bloom(path)?;
-        blooms.insert(filename, bloom);
+        if path.is_dir() {
+            resolved.extend(resolve_bloom_directory(path, tags)?);
+        } else if !tags.is_empty() {
+            return Err(format!(
+                "{}: Tags can only be used on directories with a {} file",
+                path.display(),
+                CATALOG_FILENAME
+            ));
+        } else {
+            resolved.push((get_filename_from_path(path)?, path.clone()));
+        }
+    }
+    // bloom filters are reported by name, which must then identify a single one
+    for (index, (name, path)) in resolved.iter().enumerate() {
+        if let Some((_, other)) = resolved[..index].iter().find(|(other, _)| other == name) {
+            return Err(format!(
+                "{} and {}: Two bloom filters are named {}, rename one of them or give it a label in a {} file",
+                other.display(),
+                path.display(),
+                name,
+                CATALOG_FILENAME
+            ));
+        }
+    }
+    Ok(resolved)
+}
+
+fn resolve_bloom_directory(dir: &Path, tags: &[String]) -> Result<Vec<(String, PathBuf)>, String> {
+    let catalog_path: PathBuf = dir.join(CATALOG_FILENAME);
+    let mut resolved: Vec<(String, PathBuf)> = Vec::new();
+    if catalog_path.is_file() {
+        let catalog: Catalog = read_catalog(&catalog_path)?;
+        for entry in catalog.filters {
+            if !entry.enabled {
+                continue;
+            }
+            if !tags.is_empty() && !entry.tags.iter().any(|tag| tags.contains(tag)) {
+                continue;
+            }
+            let path: PathBuf = dir.join(&entry.path);
+            let name: String = match entry.label {
+                Some(label) => label,
+                None => get_filename_from_path(&path)?,
+            };
+            resolved.push((name, path));
+        }
+        return Ok(resolved);
+    }
+    if !tags.is_empty() {
+        return Err(format!(
+            "{}: Tags can only be used on directories with a {} file",
+            dir.display(),
+            CATALOG_FILENAME
+        ));
+    }
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => return Err(format!("{}: {}", dir.display(), e)),
+    };
+    for entry in entries {
+        let path: PathBuf = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => return Err(format!("{}: {}", dir.display(), e)),
+        };
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "bloom") {
+            resolved.push((get_filename_from_path(&path)?, path));
+        }
+    }
+    resolved.sort();
+    Ok(resolved)
+}
+
+pub fn get_bloom_from_paths(
+    bloom_paths: &Vec<PathBuf>,
+    tags: &[String],
+) -> Result<HashMap<String, BloomFile>, String> {
+    let mut blooms: HashMap<String, BloomFile> = HashMap::new();
+    for (name, path) in resolve_bloom_paths(bloom_paths, tags)? {
+        let bloom_file = deserialize_bloom_file(&path)?;
+        blooms.insert(name, bloom_file);
     }
     Ok(blooms)
 }
@@ -332,11 +1020,19 @@ pub fn get_bloom_from_queryhashes(
     queryhashes: &Vec<String>,
     environment: &String,
     rate: f64,
-) -> Result<HashMap<String, Bloom<String>>, String> {
-    let mut blooms: HashMap<String, Bloom<String>> = HashMap::new();
+    expires_in_days: Option<u64>,
+) -> Result<HashMap<String, BloomFile>, String> {
+    let mut blooms: HashMap<String, BloomFile> = HashMap::new();
     for queryhash in queryhashes {
-        let bloom = create_bloom_from_queryhash(queryhash.to_string(), environment, rate)?;
-        blooms.insert(queryhash.to_string(), bloom);
+        let atom_values: Vec<String> = get_values_from_queryhash(queryhash.clone(), environment)?;
+        let atom_types: Vec<AtomType> = get_atom_types(&classify_values(atom_values.clone()));
+        let (atom_values, ranges) = split_ip_ranges(atom_values);
+        let size: usize = atom_values.len().max(1);
+        let mut bloom_file = BloomFile::new(create_bloom(atom_values, size, rate), expires_in_days);
+        bloom_file.metadata.atom_types = Some(atom_types);
+        bloom_file.metadata.rate = Some(rate);
+        bloom_file.ranges = ranges;
+        blooms.insert(queryhash.to_string(), bloom_file);
     }
     Ok(blooms)
 }
@@ -344,36 +1040,304 @@ pub fn get_bloom_from_queryhashes(
 pub fn check_val_in_bloom(bloom: Bloom<String>, input: &Vec<String>) -> Vec<String> {
     let mut matches: Vec<String> = Vec::new();
     for value in input {
-        if bloom.check(value) {
+        if bloom.check(&canonicalize(value)) {
             matches.push(value.to_string());
         }
     }
     matches
 }
 
+#[derive(Clone, Debug, Default)]
+pub struct CheckOptions {
+    pub parent_domains: bool,
+    pub url_components: bool,
+}
+
+// canonical values to check for an input value with their type, the most specific first
+fn get_check_candidates(
+    input_value: &InputValue,
+    options: &CheckOptions,
+) -> Vec<(MatchedComponent, InputValue)> {
+    let canonical: String = canonicalize(&input_value.value);
+    let mut candidates: Vec<(MatchedComponent, InputValue)> = vec![(
+        MatchedComponent::Value,
+        InputValue {
+            value: canonical.clone(),
+            atom_type: input_value.atom_type,
+            context: Vec::new(),
+        },
+    )];
+    if options.parent_domains && input_value.atom_type == AtomType::Fqdn {
+        for parent in parent_domains(&canonical) {
+            candidates.push((MatchedComponent::ParentDomain, InputValue::new(parent)));
+        }
+    }
+    if options.url_components && input_value.atom_type == AtomType::Url {
+        let components: UrlComponents = decompose_url(&canonical);
+        if let Some(ip) = components.ip {
+            candidates.push((MatchedComponent::Ip, InputValue::new(ip.to_string())));
+        }
+        if let Some(host) = components.host {
+            let parents: Vec<String> = if options.parent_domains {
+                parent_domains(&host)
+            } else {
+                Vec::new()
+            };
+            candidates.push((MatchedComponent::Host, InputValue::new(host)));
+            // the registered domain is checked last as its own component
+            for parent in parents {
+                if Some(&parent) != components.registered_domain.as_ref() {
+                    candidates.push((MatchedComponent::ParentDomain, InputValue::new(parent)));
+                }
+            }
+        }
+        if let Some(registered_domain) = components.registered_domain {
+            candidates.push((
+                MatchedComponent::RegisteredDomain,
+                InputValue::new(registered_domain),
+            ));
+        }
+    }
+    candidates
+}
+
+// hosts and IPs of the URLs, to create bloom filters matching them. Their registered domains
+// are not added, as a single URL on a shared service such as sites.google.com would make every
+// google.com value match
+pub fn add_url_components(values: Vec<String>) -> Vec<String> {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut expanded: Vec<String> = Vec::new();
+    for value in values {
+        let mut components: Vec<String> = Vec::new();
+        if detect_atom_type(&value) == AtomType::Url {
+            let url_components: UrlComponents = decompose_url(&value);
+            components.extend(url_components.host);
+            components.extend(url_components.ip.map(|ip| ip.to_string()));
+        }
+        for value in std::iter::once(value).chain(components) {
+            if seen.insert(value.clone()) {
+                expanded.push(value);
+            }
+        }
+    }
+    expanded
+}
+
+// values of a type the bloom filter was not created with are not checked, to avoid false positives
+pub fn check_values_in_bloom_file(
+    bloom_file: &BloomFile,
+    input: &[InputValue],
+    options: &CheckOptions,
+) -> Vec<BloomMatch> {
+    let mut checker = BloomChecker::new(bloom_file);
+    input
+        .iter()
+        .filter_map(|input_value| checker.check(input_value, options))
+        .collect()
+}
+
+pub struct BloomChecker<'a> {
+    bloom_file: &'a BloomFile,
+    range_index: IpRangeIndex,
+    nb_skipped: usize,
+    nb_skipped_matches: usize,
+}
+
+impl<'a> BloomChecker<'a> {
+    pub fn new(bloom_file: &'a BloomFile) -> Self {
+        BloomChecker {
+            bloom_file,
+            range_index: IpRangeIndex::new(&bloom_file.ranges),
+            nb_skipped: 0,
+            nb_skipped_matches: 0,
+        }
+    }
+
+    pub fn check(
+        &mut self,
+        input_value: &InputValue,
+        options: &CheckOptions,
+    ) -> Option<BloomMatch> {
+        let candidates: Vec<(MatchedComponent, InputValue)> =
+            get_check_candidates(input_value, options);
+        let metadata: &BloomMetadata = &self.bloom_file.metadata;
+        let found = candidates
+            .iter()
+            .find(|(_, candidate)| {
+                metadata.accepts(candidate.atom_type)
+                    && self.bloom_file.bloom.check(&candidate.value)
+            })
+            .map(|(component, candidate)| (*component, candidate.value.clone()))
+            .or_else(|| find_in_ip_ranges(&self.range_index, &candidates));
+        let (component, matched_value) = match found {
+            Some(found) => found,
+            None => {
+                self.count_skipped(&candidates);
+                return None;
+            }
+        };
+        Some(BloomMatch {
+            input: input_value.clone(),
+            matched_value,
+            matched_component: component,
+        })
+    }
+
+    // counts the values not checked because of their type, and among them the ones the bloom
+    // filter contains, which are either false positives or values detected as the wrong type
+    fn count_skipped(&mut self, candidates: &[(MatchedComponent, InputValue)]) {
+        let metadata: &BloomMetadata = &self.bloom_file.metadata;
+        let skipped: Vec<&InputValue> = candidates
+            .iter()
+            .map(|(_, candidate)| candidate)
+            .filter(|candidate| !metadata.accepts(candidate.atom_type))
+            .collect();
+        if skipped.len() < candidates.len() {
+            return;
+        }
+        self.nb_skipped += 1;
+        if skipped
+            .iter()
+            .any(|candidate| self.bloom_file.bloom.check(&candidate.value))
+        {
+            self.nb_skipped_matches += 1;
+        }
+    }
+
+    // number of values not checked as none of their candidates has a type of the bloom filter
+    pub fn nb_skipped(&self) -> usize {
+        self.nb_skipped
+    }
+
+    // number of the skipped values which would have matched regardless of their type
+    pub fn nb_skipped_matches(&self) -> usize {
+        self.nb_skipped_matches
+    }
+}
+
+fn find_in_ip_ranges(
+    range_index: &IpRangeIndex,
+    candidates: &[(MatchedComponent, InputValue)],
+) -> Option<(MatchedComponent, String)> {
+    if range_index.is_empty() {
+        return None;
+    }
+    candidates.iter().find_map(|(_, candidate)| {
+        let network: IpNet = match candidate.atom_type {
+            AtomType::Ipv4 | AtomType::Ipv6 => candidate.value.parse::<IpAddr>().ok()?.into(),
+            // ranges spanning several CIDR blocks are only looked up as a whole in the bloom filter
+            AtomType::IpRange => match parse_ip_range(&candidate.value)?.as_slice() {
+                [network] => *network,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        range_index
+            .find(&network)
+            .map(|range| (MatchedComponent::Range, range.to_string()))
+    })
+}
+
+pub struct VerifyReport {
+    pub nb_checked: usize,
+    pub missing: Vec<String>,
+    pub nb_samples: usize,
+    pub nb_false_positives: usize,
+}
+
+impl VerifyReport {
+    pub fn observed_rate(&self) -> f64 {
+        if self.nb_samples == 0 {
+            return 0.0;
+        }
+        self.nb_false_positives as f64 / self.nb_samples as f64
+    }
+}
+
+pub fn verify_bloom(bloom: &Bloom<String>, source: &[String], nb_samples: usize) -> VerifyReport {
+    let mut spinner = Spinner::new(Spinners::Line, "Verifying bloom filter".to_string());
+    let mut missing: Vec<String> = Vec::new();
+    for value in source {
+        if !bloom.check(&canonicalize(value)) {
+            missing.push(value.to_string());
+        }
+    }
+    let known_values: HashSet<&String> = source.iter().collect();
+    let mut seed: u64 = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or(0)
+        | 1;
+    let mut nb_false_positives: usize = 0;
+    let mut nb_tested: usize = 0;
+    while nb_tested < nb_samples {
+        let sample: String = random_sample_value(&mut seed);
+        if known_values.contains(&sample) {
+            continue;
+        }
+        nb_tested += 1;
+        if bloom.check(&sample) {
+            nb_false_positives += 1;
+        }
+    }
+    spinner.stop_and_persist("✔", "Done verifying bloom filter.".into());
+    VerifyReport {
+        nb_checked: source.len(),
+        missing,
+        nb_samples,
+        nb_false_positives,
+    }
+}
+
+fn random_sample_value(state: &mut u64) -> String {
+    let mut sample = String::with_capacity(32);
+    for _ in 0..2 {
+        // xorshift64, good enough to generate values absent from the source
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        sample.push_str(&format!("{:016x}", state));
+    }
+    sample
+}
+
+// values are looked up separately for each of their Datalake atom types, so that Datalake
+// doesn't detect another type for them, e.g. for hashes which can be of several atom types
 pub fn lookup_values_in_dtl(
-    atom_values: Vec<String>,
+    values_by_type: &BTreeMap<&str, Vec<String>>,
     environment: &String,
-    treat_hashes_like: &String,
 ) -> Result<String, String> {
     let mut dtl: Datalake = match init_datalake(environment) {
         Ok(dtl) => dtl,
         Err(e) => return Err(format!("{}", e)),
     };
     let mut sp = Spinner::with_timer(Spinners::Line, "Waiting for data from Datalake...".into());
-    let csv_result: String = match dtl.bulk_lookup(atom_values, treat_hashes_like) {
-        Ok(csv_result) => {
-            sp.stop_and_persist("✔", "Successfully fetched data from Datalake!".into());
-            csv_result
-        }
-        Err(e) => {
-            sp.stop_and_persist("✗", "Failed to fetch data from Datalake.".into());
-            return Err(format!("{}", e));
+    let mut csv_result: String = String::new();
+    for (atom_type, atom_values) in values_by_type {
+        match dtl.bulk_lookup(atom_values.clone(), atom_type) {
+            Ok(csv) => append_lookup_result(&mut csv_result, &csv),
+            Err(e) => {
+                sp.stop_and_persist("✗", "Failed to fetch data from Datalake.".into());
+                return Err(format!("{}", e));
+            }
         }
-    };
+    }
+    sp.stop_and_persist("✔", "Successfully fetched data from Datalake!".into());
     Ok(csv_result)
 }
 
+// appends the rows of a lookup result, results all having the same header
+fn append_lookup_result(merged: &mut String, csv: &str) {
+    if merged.is_empty() {
+        merged.push_str(csv);
+    } else if let Some((_, body)) = csv.split_once('\n') {
+        merged.push_str(body);
+    }
+    if !merged.is_empty() && !merged.ends_with('\n') {
+        merged.push('\n');
+    }
+}
+
 pub fn count_lookup_result_nb_lines(csv: &String) -> usize {
     let mut reader = Reader::from_reader(csv.as_bytes());
     let mut nb_lines = 0;
@@ -383,6 +1347,15 @@ pub fn count_lookup_result_nb_lines(csv: &String) -> usize {
     nb_lines
 }
 
+#[test]
+fn test_append_lookup_result() {
+    let mut merged: String = String::new();
+    append_lookup_result(&mut merged, "atom_value,.hashes.md5\n10.0.0.1,\n");
+    append_lookup_result(&mut merged, "atom_value,.hashes.md5\nevil.com,");
+    append_lookup_result(&mut merged, "atom_value,.hashes.md5\n");
+    assert_eq!(merged, "atom_value,.hashes.md5\n10.0.0.1,\nevil.com,\n");
+}
+
 #[test]
 fn test_dtl_csv_resp_to_vec() {
     let csv_string: String = "atom_value,.hashes.md5,.hashes.sha1,.hashes.sha256\na50cb264d1979be3b3d766c0a7061372,a50cb264d1979be3b3d766c0a7061372,abe46855df32b6b46b71719e6d2d03c24285d1f4,b46e51a2e757f4d75f1a1fff1165c6a0503b687db6c7e672021dcaa9bedf2d88\n3005c03a7520a2db1f317c7551773355,3005c03a7520a2db1f317c7551773355,f7e5581cfb45c23d88951bd6afb47fc96fc7cd4b,\n188.227.106.122,,,\n1cdadad999b9e70c87560fcd9821c2b0fa4c0a92b8f79bded44935dd4fdc76a5,,,1cdadad999b9e70c87560fcd9821c2b0fa4c0a92b8f79bded44935dd4fdc76a5".to_string();
@@ -1,15 +1,36 @@
-use bloomfilter::Bloom;
 use clap::{ArgGroup, Args, Parser, Subcommand};
 use colored::*;
+use dtl_hunter::access_log::LogFormat;
+use dtl_hunter::atom_type::AtomType;
+use dtl_hunter::bundle::{export_bundle, import_bundle, read_signing_key};
+use dtl_hunter::canonical::CANONICALIZATION_VERSION;
+use dtl_hunter::encoding::parse_encoding;
+use dtl_hunter::ip_ranges::split_ip_ranges;
+use dtl_hunter::jsonl::JsonPath;
+use dtl_hunter::patch::{
+    apply_patch, diff_blooms, read_patch_file, write_patch_to_file, BloomPatch,
+};
 use dtl_hunter::{
-    check_val_in_bloom, count_lookup_result_nb_lines, get_bloom_from_paths,
-    get_bloom_from_queryhashes, lookup_values_in_dtl, read_input_file, write_bloom_to_file,
-    write_csv, write_file,
+    add_url_components, classify_values, count_lookup_result_nb_lines, create_bloom_with_seed,
+    deserialize_bloom_file, for_each_input_value, get_atom_types, get_bloom_from_paths,
+    get_bloom_from_queryhashes, get_values_from_file, get_values_from_queryhash,
+    has_datalake_credentials, lookup_values_in_dtl, now_timestamp, resolve_bloom_paths,
+    seed_from_passphrase, verify_bloom, write_bloom_to_file, write_file, BloomChecker, BloomFile,
+    BloomMatch, BloomMetadata, CheckOptions, InputFormat, InputOptions, InputValue, MatchWriter,
+    VerifyReport, STDIN_PATH,
 };
+use encoding_rs::Encoding;
+use ipnet::IpNet;
 use log::{error, info, warn};
 use spinners::{Spinner, Spinners};
-use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// number of lines listed in the warning about undecodable bytes
+const MAX_REPORTED_LINES: usize = 10;
+
 #[derive(Parser)]
 #[clap(
     name = "Datalake Hunter",
@@ -18,7 +39,6 @@ use std::path::PathBuf;
     about = "Allow to mass check data from datalake using bloom filters.",
     long_about = None
 )]
-
 // #[clap(propagate_version = true)]
 struct Cli {
     #[clap(subcommand)]
@@ -39,13 +59,18 @@ enum Commands {
     Check(Check),
     Create(Create),
     Lookup(Lookup),
+    Verify(Verify),
+    Diff(Diff),
+    Patch(Patch),
+    ExportBundle(ExportBundle),
+    ImportBundle(ImportBundle),
 }
 
 #[derive(Args)]
 #[clap(
     about = "Checks if values in the provided file can be found in bloom filters or in Datalake using query hashes."
 )]
-#[clap(group(ArgGroup::new("bloom_filter_group").required(true).multiple(true).args(&["bloom", "queryhash"])))]
+#[clap(group(ArgGroup::new("bloom_filter_group").required(true).multiple(true).args(&["bloom", "bloom-dir", "queryhash"])))]
 struct Check {
     #[clap(
         short,
@@ -60,7 +85,7 @@ struct Check {
         long,
         value_parser,
         forbid_empty_values = true,
-        help = "Path to file containing the values to check, one value per line or the values from the first column in a CSV."
+        help = "Path to file containing the values to check, one value per line or the values from the first column in a CSV, or `-` to read from stdin."
     )]
     input: PathBuf,
     #[clap(
@@ -71,6 +96,21 @@ struct Check {
         help = "Path to a bloom filter to be used for the check. Required if no query hashes are provided"
     )]
     bloom: Option<Vec<PathBuf>>,
+    #[clap(
+        short = 'B',
+        long = "bloom-dir",
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to a directory of bloom filters to be used for the check. If the directory contains a catalog.ron file, only the enabled bloom filters it lists are used."
+    )]
+    bloom_dir: Option<Vec<PathBuf>>,
+    #[clap(
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Only use the bloom filters of the directories' catalogs having this tag."
+    )]
+    tag: Option<Vec<String>>,
     #[clap(
         short,
         long,
@@ -91,6 +131,11 @@ struct Check {
     quiet: bool,
     #[clap(long = "no-header", help = "Remove the header in the output csv file.")]
     no_header: bool,
+    #[clap(
+        long,
+        help = "Defang the matched values in the output, e.g. hxxp://evil[.]com, so they are safe to share."
+    )]
+    defang: bool,
     #[clap(
         short,
         long,
@@ -105,6 +150,31 @@ struct Check {
         help = "Enable saving bloom filters created from the query hashes"
     )]
     save: bool,
+    #[clap(
+        long = "expires-in",
+        value_parser,
+        help = "Number of days after which the bloom filters saved with `--save` expire."
+    )]
+    expires_in: Option<u64>,
+    #[clap(
+        long = "max-age",
+        value_parser,
+        default_value = "30",
+        help = "Age in days above which a warning is emitted for a bloom filter."
+    )]
+    max_age: u64,
+    #[clap(long, help = "Refuse to check values against expired bloom filters.")]
+    strict: bool,
+    #[clap(
+        long = "parent-domains",
+        help = "Also check the parent domains of FQDNs, up to their registered domain, e.g. login.evil.com and evil.com for cdn.login.evil.com."
+    )]
+    parent_domains: bool,
+    #[clap(
+        long = "url-components",
+        help = "Also check the host, the registered domain or the IP of URLs, e.g. cdn.evil.com and evil.com for https://cdn.evil.com/a.php."
+    )]
+    url_components: bool,
     #[clap(
         short,
         long,
@@ -113,6 +183,8 @@ struct Check {
         help = "Specifies which atom type should hashes be interpreted as. See Datalake API documentation for possible values"
     )]
     treat_hashes_like: String,
+    #[clap(flatten)]
+    input_args: InputArgs,
 }
 
 #[derive(Args)]
@@ -140,7 +212,7 @@ struct Create {
         long,
         value_parser,
         forbid_empty_values = true,
-        help = "Path to file to use to create a bloom filter, one value per line or the values from the first column in a CSV."
+        help = "Path to file to use to create a bloom filter, one value per line or the values from the first column in a CSV, or `-` to read from stdin."
     )]
     file: Option<std::path::PathBuf>,
     #[clap(
@@ -152,6 +224,32 @@ struct Create {
         help = "Rate of false positive. Can be between 0.0 and 1.0. The lower the rate the bigger the bloom filter will be."
     )]
     rate: f64,
+    #[clap(
+        long = "expires-in",
+        value_parser,
+        help = "Number of days after which the created bloom filter expires."
+    )]
+    expires_in: Option<u64>,
+    #[clap(
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Passphrase from which the bloom filter hash keys are derived. Bloom filters created with the same seed and capacity can be diffed."
+    )]
+    seed: Option<String>,
+    #[clap(
+        long,
+        value_parser,
+        help = "Number of values the bloom filter is sized for. Default is the number of values used to create it."
+    )]
+    capacity: Option<usize>,
+    #[clap(
+        long = "url-components",
+        help = "Also add the host or the IP of URLs to the bloom filter."
+    )]
+    url_components: bool,
+    #[clap(flatten)]
+    input_args: InputArgs,
 }
 
 #[derive(Args)]
@@ -162,7 +260,7 @@ struct Lookup {
         long,
         value_parser,
         forbid_empty_values = true,
-        help = "Path to file containing the values to lookup, one value per line or the values from the first column in a CSV."
+        help = "Path to file containing the values to lookup, one value per line or the values from the first column in a CSV, or `-` to read from stdin."
     )]
     input: PathBuf,
     #[clap(
@@ -181,6 +279,312 @@ struct Lookup {
         help = "Specifies which atom type should hashes be interpreted as. See Datalake API documentation for possible values"
     )]
     treat_hashes_like: String,
+    #[clap(flatten)]
+    input_args: InputArgs,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Verifies that a bloom filter still contains every value of the file or query hash it was built from."
+)]
+#[clap(group(ArgGroup::new("verify_use_either").required(true).args(&["file", "queryhash"])))]
+struct Verify {
+    #[clap(
+        short,
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to the bloom filter to verify."
+    )]
+    bloom: PathBuf,
+    #[clap(
+        short,
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to the file the bloom filter was created from, one value per line or the values from the first column in a CSV."
+    )]
+    file: Option<PathBuf>,
+    #[clap(
+        short,
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Query hash the bloom filter was created from."
+    )]
+    queryhash: Option<String>,
+    #[clap(
+        short,
+        long,
+        value_parser =  validate_false_positive,
+        forbid_empty_values = true,
+        help = "Rate of false positive requested when the bloom filter was created. Default is the rate recorded in the bloom filter."
+    )]
+    rate: Option<f64>,
+    #[clap(
+        short,
+        long,
+        value_parser,
+        default_value = "100000",
+        help = "Number of values absent from the source to test in order to measure the false positive rate."
+    )]
+    samples: usize,
+    #[clap(flatten)]
+    input_args: InputArgs,
+}
+
+#[derive(Args)]
+#[clap(about = "Creates a patch between two versions of a bloom filter.")]
+struct Diff {
+    #[clap(
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to the previous version of the bloom filter."
+    )]
+    old: PathBuf,
+    #[clap(
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to the new version of the bloom filter."
+    )]
+    new: PathBuf,
+    #[clap(
+        short,
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to the file to output the patch. Default is the new bloom filter's name with the `.patch` extension."
+    )]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+#[clap(about = "Applies a patch created with the diff command to a bloom filter.")]
+struct Patch {
+    #[clap(
+        short,
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to the bloom filter to patch."
+    )]
+    bloom: PathBuf,
+    #[clap(
+        short,
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to the patch to apply."
+    )]
+    patch: PathBuf,
+    #[clap(
+        short,
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to the file to output the patched bloom filter. Default is to replace the patched bloom filter."
+    )]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Packages bloom filters with their metadata and checksums into a single archive to transfer them."
+)]
+#[clap(group(ArgGroup::new("export_bloom_group").required(true).multiple(true).args(&["bloom", "bloom-dir"])))]
+struct ExportBundle {
+    #[clap(
+        short,
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to a bloom filter to add to the bundle."
+    )]
+    bloom: Option<Vec<PathBuf>>,
+    #[clap(
+        short = 'B',
+        long = "bloom-dir",
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to a directory of bloom filters to add to the bundle. If the directory contains a catalog.ron file, only the enabled bloom filters it lists are added."
+    )]
+    bloom_dir: Option<Vec<PathBuf>>,
+    #[clap(
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Only add the bloom filters of the directories' catalogs having this tag."
+    )]
+    tag: Option<Vec<String>>,
+    #[clap(
+        short,
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to the file to output the bundle."
+    )]
+    output: PathBuf,
+    #[clap(
+        short,
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to a file containing the secret key used to sign the bloom filters."
+    )]
+    key: Option<PathBuf>,
+}
+
+#[derive(Args)]
+#[clap(
+    about = "Verifies a bundle created with the export-bundle command and installs its bloom filters in a directory."
+)]
+struct ImportBundle {
+    #[clap(
+        short,
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to the bundle to import."
+    )]
+    input: PathBuf,
+    #[clap(
+        short,
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to the directory in which to install the bloom filters."
+    )]
+    directory: PathBuf,
+    #[clap(
+        short,
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to a file containing the secret key used to verify the signatures of the bloom filters. Unsigned bundles are rejected when it is set."
+    )]
+    key: Option<PathBuf>,
+    #[clap(
+        long,
+        help = "Replace the bloom filters already installed in the directory."
+    )]
+    force: bool,
+}
+
+#[derive(Args)]
+struct InputArgs {
+    #[clap(
+        long,
+        conflicts_with = "format",
+        help = "Extract the IPs, domains, URLs, email addresses and hashes found in the text of the input file instead of reading it as a CSV. Can't be used with `--format`."
+    )]
+    extract: bool,
+    #[clap(
+        long,
+        value_enum,
+        default_value = "csv",
+        help = "Format of the input file: `csv`, `zeek` for Zeek logs in the TSV or JSON format, `suricata` for Suricata eve.json files, `jsonl` for JSON Lines read with `--field`, `pcap` for pcap and pcapng captures, `evtx` for Windows event logs, `squid` for native Squid logs, `combined` for Apache, nginx and Squid combined logs, `access-log` for access logs read with `--log-format`, `bind`, `dnsmasq`, `unbound` and `windows-dns` for the query logs of DNS resolvers, `eml` for emails and mbox files, or `xlsx` and `ods` for spreadsheets."
+    )]
+    format: InputFormat,
+    #[clap(
+        long = "unknown-output",
+        value_parser,
+        forbid_empty_values = true,
+        help = "Path to the file in which to list the input values whose type could not be detected."
+    )]
+    unknown_output: Option<PathBuf>,
+    #[clap(
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Name or index, starting from 0, of a column of the input CSV file or spreadsheet to read. Default is the first column."
+    )]
+    column: Option<Vec<String>>,
+    #[clap(
+        long,
+        value_parser,
+        forbid_empty_values = true,
+        help = "Name or index, starting from 0, of a sheet of the input spreadsheet to read. Default is all the sheets."
+    )]
+    sheet: Option<Vec<String>>,
+    #[clap(
+        long,
+        value_parser = parse_delimiter,
+        default_value = ",",
+        help = "Delimiter of the input CSV file, a single character or `tab`."
+    )]
+    delimiter: u8,
+    #[clap(
+        long,
+        help = "Skip the first row of the input CSV file or spreadsheet as a header. Implied when a column is selected by name."
+    )]
+    header: bool,
+    #[clap(
+        long,
+        value_parser = parse_comment,
+        help = "Character starting the comment lines of the input CSV file, which are skipped. Default is to skip no line."
+    )]
+    comment: Option<u8>,
+    #[clap(
+        long,
+        value_parser = JsonPath::parse,
+        help = "Path to a field of the JSON Lines records to read, such as /dns/answers/data. Arrays are traversed. Required with `--format jsonl`."
+    )]
+    field: Option<Vec<JsonPath>>,
+    #[clap(
+        long = "context-field",
+        value_parser = JsonPath::parse,
+        help = "Path to a field of the JSON Lines records to show in the context of their values."
+    )]
+    context_field: Option<Vec<JsonPath>>,
+    #[clap(
+        long = "log-format",
+        value_parser = LogFormat::parse,
+        help = "Format of the lines of the access log, with Apache directives such as `%h %t \"%r\" %>s` or nginx variables such as `$remote_addr [$time_local] \"$request\" $status`. Required with `--format access-log`."
+    )]
+    log_format: Option<LogFormat>,
+    #[clap(
+        long,
+        value_parser = parse_encoding,
+        help = "Encoding of the input text, such as `utf-16le` or `windows-1252`. Default is UTF-8, or the encoding given by the BOM of the input."
+    )]
+    encoding: Option<&'static Encoding>,
+}
+
+impl InputArgs {
+    fn options(&self) -> InputOptions {
+        InputOptions {
+            extract: self.extract,
+            format: self.format,
+            columns: self.column.clone().unwrap_or_default(),
+            sheets: self.sheet.clone().unwrap_or_default(),
+            delimiter: self.delimiter,
+            header: self.header,
+            comment: self.comment,
+            fields: self.field.clone().unwrap_or_default(),
+            context_fields: self.context_field.clone().unwrap_or_default(),
+            log_format: self.log_format.clone(),
+            encoding: self.encoding,
+        }
+    }
+}
+
+fn parse_delimiter(value: &str) -> Result<u8, String> {
+    match value {
+        "tab" | "\\t" | "\t" => Ok(b'\t'),
+        _ => parse_comment(value),
+    }
+}
+
+fn parse_comment(value: &str) -> Result<u8, String> {
+    match value.as_bytes() {
+        [byte] if byte.is_ascii() => Ok(*byte),
+        _ => Err(format!(
+            "A single ASCII character is expected, {} was provided",
+            value
+        )),
+    }
 }
 
 fn validate_false_positive(value: &str) -> Result<f64, String> {
@@ -209,18 +613,63 @@ fn main() {
         Commands::Check(args) => check_command(args, &cli),
         Commands::Create(args) => create_command(args, &cli),
         Commands::Lookup(args) => lookup_command(args, &cli),
+        Commands::Verify(args) => verify_command(args, &cli),
+        Commands::Diff(args) => diff_command(args),
+        Commands::Patch(args) => patch_command(args),
+        Commands::ExportBundle(args) => export_bundle_command(args),
+        Commands::ImportBundle(args) => import_bundle_command(args),
     }
 }
 
 fn create_command(args: &Create, cli: &Cli) {
-    let bloom_result = if let Some(queryhash) = &args.queryhash {
-        dtl_hunter::create_bloom_from_queryhash(queryhash.clone(), &cli.environment, args.rate)
+    if args.output.is_none() && args.file.as_deref() == Some(Path::new(STDIN_PATH)) {
+        error!("`--output` is required when reading the values from stdin.");
+        return;
+    }
+    let values_result = if let Some(queryhash) = &args.queryhash {
+        get_values_from_queryhash(queryhash.clone(), &cli.environment)
     } else if let Some(input_path) = &args.file {
-        dtl_hunter::create_bloom_from_file(input_path, args.rate)
+        get_values_from_file(input_path, &args.input_args.options()).map(
+            |(values, replaced_lines)| {
+                warn_replaced_lines(input_path, &replaced_lines);
+                values
+            },
+        )
     } else {
         error!("Unexpected case");
         return;
     };
+    let seed: Option<[u8; 32]> = args.seed.as_deref().map(seed_from_passphrase);
+    let mut atom_types: Vec<AtomType> = Vec::new();
+    let mut ranges: Vec<IpNet> = Vec::new();
+    let bloom_result = values_result.map(|values| {
+        let values: Vec<String> = if args.url_components {
+            add_url_components(values)
+        } else {
+            values
+        };
+        let input_values: Vec<InputValue> = classify_values(values.clone());
+        report_unknown_values(&input_values, &args.input_args.unknown_output);
+        atom_types = get_atom_types(&input_values);
+        let (values, ip_ranges) = split_ip_ranges(values);
+        if !ip_ranges.is_empty() {
+            info!("{} IP ranges stored along the bloom filter", ip_ranges.len());
+        }
+        ranges = ip_ranges;
+        let capacity: usize = args.capacity.unwrap_or(values.len()).max(1);
+        if values.len() > capacity {
+            warn!(
+                "{}",
+                format!(
+                    "{} values exceed the capacity of the bloom filter ({}), its false positive rate will be higher than requested.",
+                    values.len(),
+                    capacity
+                )
+                .yellow()
+            );
+        }
+        create_bloom_with_seed(values, capacity, args.rate, seed.as_ref())
+    });
     let output_path: PathBuf = if let Some(path) = args.output.clone() {
         path
     } else {
@@ -241,15 +690,21 @@ fn create_command(args: &Create, cli: &Cli) {
         }
     };
     match bloom_result {
-        Ok(bloom) => write_bloom(&bloom, &output_path),
+        Ok(bloom) => {
+            let mut bloom_file = BloomFile::new(bloom, args.expires_in);
+            bloom_file.metadata.atom_types = Some(atom_types);
+            bloom_file.metadata.rate = Some(args.rate);
+            bloom_file.ranges = ranges;
+            write_bloom(&bloom_file, &output_path)
+        }
         Err(e) => {
             error!("Error while creating bloom filter: {}", e)
         }
     };
 }
 
-fn write_bloom(bloom: &Bloom<String>, output: &PathBuf) {
-    match write_bloom_to_file(bloom, output) {
+fn write_bloom(bloom_file: &BloomFile, output: &PathBuf) {
+    match write_bloom_to_file(bloom_file, output) {
         Ok(()) => {
             info!(
                 "{}{}",
@@ -264,18 +719,23 @@ fn write_bloom(bloom: &Bloom<String>, output: &PathBuf) {
 }
 
 fn check_command(args: &Check, cli: &Cli) {
-    let input: Vec<String> = match read_input_file(&args.input) {
-        Ok(input) => input,
-        Err(e) => {
-            error!("{}: {}", &args.input.display(), e);
-            return;
-        }
-    };
-
-    let mut blooms: HashMap<String, Bloom<String>> = HashMap::new();
+    if (args.queryhash.is_some() || args.lookup.is_some())
+        && !can_read_input_with_credentials(&args.input)
+    {
+        return;
+    }
+    let mut blooms: HashMap<String, BloomFile> = HashMap::new();
 
-    if let Some(bloom_paths) = &args.bloom {
-        let file_blooms = match get_bloom_from_paths(bloom_paths) {
+    let mut bloom_paths: Vec<PathBuf> = Vec::new();
+    if let Some(paths) = &args.bloom {
+        bloom_paths.extend(paths.clone());
+    }
+    if let Some(dirs) = &args.bloom_dir {
+        bloom_paths.extend(dirs.clone());
+    }
+    if !bloom_paths.is_empty() {
+        let tags: Vec<String> = args.tag.clone().unwrap_or_default();
+        let file_blooms = match get_bloom_from_paths(&bloom_paths, &tags) {
             Ok(file_blooms) => file_blooms,
             Err(e) => {
                 error!("{}", e);
@@ -285,48 +745,176 @@ fn check_command(args: &Check, cli: &Cli) {
         blooms.extend(file_blooms);
     }
     if let Some(queryhashes) = &args.queryhash {
-        let queryhash_blooms =
-            match get_bloom_from_queryhashes(queryhashes, &cli.environment, args.rate) {
-                Ok(queryhash_bloom) => queryhash_bloom,
-                Err(e) => {
-                    error!("{}", e);
-                    return;
-                }
-            };
+        let queryhash_blooms = match get_bloom_from_queryhashes(
+            queryhashes,
+            &cli.environment,
+            args.rate,
+            args.expires_in,
+        ) {
+            Ok(queryhash_bloom) => queryhash_bloom,
+            Err(e) => {
+                error!("{}", e);
+                return;
+            }
+        };
+        if let Some(queryhash) = queryhash_blooms
+            .keys()
+            .find(|name| blooms.contains_key(*name))
+        {
+            error!("Two bloom filters are named {}", queryhash);
+            return;
+        }
         if args.save {
-            for (queryhash, bloom) in &queryhash_blooms {
+            for (queryhash, bloom_file) in &queryhash_blooms {
                 let mut path = PathBuf::from(queryhash);
                 path.set_extension("bloom");
-                write_bloom(bloom, &path);
+                write_bloom(bloom_file, &path);
             }
         }
         blooms.extend(queryhash_blooms);
     }
 
-    let mut bloom_matches: HashMap<String, Vec<String>> = HashMap::new();
+    let now: u64 = now_timestamp();
+    let mut bloom_ages: HashMap<String, String> = HashMap::new();
+    for (filename, bloom_file) in &blooms {
+        check_bloom_canonicalization(filename, &bloom_file.metadata);
+        match check_bloom_age(
+            filename,
+            &bloom_file.metadata,
+            args.max_age,
+            args.strict,
+            now,
+        ) {
+            Ok(age) => {
+                bloom_ages.insert(filename.clone(), age);
+            }
+            Err(e) => {
+                error!("{}", e);
+                return;
+            }
+        }
+    }
+
+    let check_options = CheckOptions {
+        parent_domains: args.parent_domains,
+        url_components: args.url_components,
+    };
+    let mut checkers: Vec<(&String, BloomChecker)> = blooms
+        .iter()
+        .map(|(filename, bloom_file)| (filename, BloomChecker::new(bloom_file)))
+        .collect();
+    checkers.sort_by(|a, b| a.0.cmp(b.0));
+
+    // values are checked as they are read, matches being written out as soon as they are found
+    let mut match_writer: Option<MatchWriter> = None;
+    let mut write_error: Option<String> = None;
+    let mut stdout_writer: MatchWriter<std::io::Stdout> = MatchWriter::stdout();
+    let mut stdout_error: Option<String> = None;
+    // values of unknown type are written out as they are read too
+    let mut nb_unknown_values: usize = 0;
+    let mut unknown_writer: Option<BufWriter<File>> = None;
+    let mut unknown_error: Option<String> = None;
     let mut nb_matches: usize = 0;
     let mut matches_to_lookup: HashSet<String> = HashSet::new();
-    let mut spinner = Spinner::new(Spinners::Line, "Checking values".to_string());
-    for (filename, bloom) in blooms {
-        let matches: Vec<String> = check_val_in_bloom(bloom, &input);
-        if args.lookup.is_some() {
-            matches_to_lookup.extend(matches.clone());
+    let mut spinner: Option<Spinner> = if args.quiet {
+        Some(Spinner::new(Spinners::Line, "Checking values".to_string()))
+    } else {
+        None
+    };
+    let read_result =
+        for_each_input_value(&args.input, &args.input_args.options(), |input_value| {
+            if input_value.atom_type == AtomType::Unknown {
+                nb_unknown_values += 1;
+                if let (Some(path), None) = (&args.input_args.unknown_output, &unknown_error) {
+                    let result = match &mut unknown_writer {
+                        Some(writer) => writeln!(writer, "{}", input_value.value),
+                        None => File::create(path).and_then(|file| {
+                            let writer = unknown_writer.insert(BufWriter::new(file));
+                            writeln!(writer, "{}", input_value.value)
+                        }),
+                    };
+                    unknown_error = result.err().map(|e| format!("{}: {}", path.display(), e));
+                }
+            }
+            for (filename, checker) in &mut checkers {
+                let bloom_match: BloomMatch = match checker.check(&input_value, &check_options) {
+                    Some(bloom_match) => bloom_match,
+                    None => continue,
+                };
+                nb_matches += 1;
+                let age: &str = bloom_ages.get(*filename).map_or("", |age| age.as_str());
+                let record: Vec<String> = bloom_match.to_record(filename, age, args.defang);
+                if !args.quiet && stdout_error.is_none() {
+                    stdout_error = stdout_writer
+                        .write(&record)
+                        .and_then(|()| stdout_writer.flush())
+                        .err();
+                }
+                if let (Some(output), None) = (&args.output, &write_error) {
+                    let result = match &mut match_writer {
+                        Some(writer) => writer.write(&record),
+                        None => {
+                            MatchWriter::create(output, args.no_header).and_then(|mut writer| {
+                                let result = writer.write(&record);
+                                match_writer = Some(writer);
+                                result
+                            })
+                        }
+                    };
+                    write_error = result.err();
+                }
+                if args.lookup.is_some() {
+                    matches_to_lookup.insert(bloom_match.matched_value);
+                }
+            }
+        });
+    if let Some(mut spinner) = spinner.take() {
+        spinner.stop_and_persist("✔", "Done checking values.".into());
+    }
+    if let Some(e) = stdout_error {
+        error!("{}", e);
+    }
+    match read_result {
+        Ok(replaced_lines) => warn_replaced_lines(&args.input, &replaced_lines),
+        Err(e) => {
+            error!("{}: {}", &args.input.display(), e);
+            return;
         }
-        nb_matches += matches.len();
-        bloom_matches.insert(filename, matches);
     }
-    spinner.stop_and_persist("✔", "Done checking values.".into());
-    manage_check_output(
-        &args.output,
-        bloom_matches,
-        args.quiet,
-        args.no_header,
-        nb_matches,
+    if let Some(e) = unknown_error.or_else(|| {
+        let path: &PathBuf = args.input_args.unknown_output.as_ref()?;
+        let e = unknown_writer.as_mut()?.flush().err()?;
+        Some(format!("{}: {}", path.display(), e))
+    }) {
+        error!("{}", e);
+    } else {
+        warn_unknown_values(nb_unknown_values, &args.input_args.unknown_output);
+    }
+    for (filename, checker) in &checkers {
+        report_skipped_values(filename, checker);
+    }
+    info!(
+        "{}",
+        format!("{} matches", &nb_matches).bright_blue().bold(),
     );
+    if let Some(output) = &args.output {
+        if let Some(e) = write_error.or_else(|| match_writer.as_mut()?.flush().err()) {
+            error!("{}", e);
+        } else if match_writer.is_some() {
+            info!(
+                "{} {}",
+                "Results saved in".green().bold(),
+                &output.display()
+            )
+        } else {
+            warn!("{}", "No matches, output file was not created".yellow());
+        }
+    }
 
     if let Some(lookup_path) = &args.lookup {
         if !matches_to_lookup.is_empty() {
-            let matches_to_lookup: Vec<String> = matches_to_lookup.into_iter().collect();
+            let matches_to_lookup: Vec<InputValue> =
+                classify_values(matches_to_lookup.into_iter().collect());
             manage_lookup(
                 matches_to_lookup,
                 lookup_path,
@@ -338,61 +926,161 @@ fn check_command(args: &Check, cli: &Cli) {
     }
 }
 
-fn manage_check_output(
-    output_path: &Option<PathBuf>,
-    bloom_matches: HashMap<String, Vec<String>>,
-    quiet: bool,
-    no_header: bool,
-    nb_matches: usize,
-) {
-    info!(
-        "{}",
-        format!("{} matches", &nb_matches).bright_blue().bold(),
-    );
-    if let Some(output) = output_path {
-        if nb_matches > 0 {
-            match write_csv(&bloom_matches, output, &no_header) {
-                Ok(()) => {
-                    info!(
-                        "{} {}",
-                        "Results saved in".green().bold(),
-                        &output.display()
-                    )
-                }
-                Err(e) => error!("{}", e),
-            }
-        } else {
-            warn!("{}", "No matches, output file was not created".yellow());
+fn report_skipped_values(filename: &str, checker: &BloomChecker) {
+    if checker.nb_skipped_matches() > 0 {
+        warn!(
+            "{}",
+            format!(
+                "{}: {} values are in the bloom filter but were not reported as their type is not one of the types it was created from. They may be false positives, or values detected as another type.",
+                filename,
+                checker.nb_skipped_matches()
+            )
+            .yellow()
+        );
+    }
+    if checker.nb_skipped() > 0 {
+        info!(
+            "{}: {} values not checked as their type is not one of the types of the bloom filter",
+            filename,
+            checker.nb_skipped()
+        );
+    }
+}
+
+// the Datalake credentials are prompted for on stdin when they are not in the environment
+fn can_read_input_with_credentials(input: &Path) -> bool {
+    if input == Path::new(STDIN_PATH) && !has_datalake_credentials() {
+        error!(
+            "Datalake credentials can't be prompted for while the input is read from stdin, set OCD_DTL_RS_LONG_TERM_TOKEN, or OCD_DTL_RS_USERNAME and OCD_DTL_RS_PASSWORD."
+        );
+        return false;
+    }
+    true
+}
+
+fn check_bloom_canonicalization(filename: &str, metadata: &BloomMetadata) {
+    if metadata.canonicalization != Some(CANONICALIZATION_VERSION) {
+        warn!(
+            "{}",
+            format!(
+                "{}: The bloom filter was created with another canonicalization of the values, some matches may be missed. Recreate it to fix this.",
+                filename
+            )
+            .yellow()
+        );
+    }
+}
+
+fn check_bloom_age(
+    filename: &str,
+    metadata: &BloomMetadata,
+    max_age: u64,
+    strict: bool,
+    now: u64,
+) -> Result<String, String> {
+    if metadata.is_expired(now) {
+        if strict {
+            return Err(format!(
+                "{}: The bloom filter has expired, recreate it or remove `--strict` to use it anyway.",
+                filename
+            ));
         }
+        warn!(
+            "{}",
+            format!("{}: The bloom filter has expired.", filename).yellow()
+        );
     }
-    if !quiet {
-        for (filename, values) in bloom_matches {
-            for val in values {
-                println!("{},{}", val, filename);
+    match metadata.age_in_days(now) {
+        Some(age) => {
+            if age > max_age {
+                warn!(
+                    "{}",
+                    format!(
+                        "{}: The bloom filter was created {} days ago, its data may be stale.",
+                        filename, age
+                    )
+                    .yellow()
+                );
             }
+            Ok(age.to_string())
+        }
+        None => {
+            warn!(
+                "{}",
+                format!(
+                    "{}: The bloom filter has no creation date, its age is unknown.",
+                    filename
+                )
+                .yellow()
+            );
+            Ok(String::new())
         }
     }
 }
 
 fn lookup_command(args: &Lookup, cli: &Cli) {
-    let input: Vec<String> = match read_input_file(&args.input) {
-        Ok(input) => input,
+    if !can_read_input_with_credentials(&args.input) {
+        return;
+    }
+    let mut input: Vec<InputValue> = Vec::new();
+    let read_result =
+        for_each_input_value(&args.input, &args.input_args.options(), |input_value| {
+            input.push(input_value)
+        });
+    match read_result {
+        Ok(replaced_lines) => warn_replaced_lines(&args.input, &replaced_lines),
         Err(e) => {
             error!("{}: {}", &args.input.display(), e);
             return;
         }
     };
-    manage_lookup(input, &args.output, &cli.environment, None, &args.treat_hashes_like);
+    report_unknown_values(&input, &args.input_args.unknown_output);
+    manage_lookup(
+        input,
+        &args.output,
+        &cli.environment,
+        None,
+        &args.treat_hashes_like,
+    );
 }
 
 fn manage_lookup(
-    input: Vec<String>,
+    input: Vec<InputValue>,
     output: &PathBuf,
     environment: &String,
     nb_matches: Option<usize>,
-    treat_hashes_like: &String,
+    treat_hashes_like: &str,
 ) {
-    let lookup_csv_string = match lookup_values_in_dtl(input, environment, treat_hashes_like) {
+    // values without a Datalake atom type would be rejected by the API
+    let mut values_by_type: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    let mut nb_skipped: usize = 0;
+    for input_value in input {
+        match input_value.atom_type.datalake_atom_type(treat_hashes_like) {
+            Some(atom_type) => values_by_type
+                .entry(atom_type)
+                .or_default()
+                .push(input_value.value),
+            None => nb_skipped += 1,
+        }
+    }
+    if nb_skipped > 0 {
+        warn!(
+            "{}",
+            format!(
+                "{} values of unknown type or IP ranges can't be looked up and were skipped.",
+                nb_skipped
+            )
+            .yellow()
+        );
+    }
+    if values_by_type.is_empty() {
+        warn!("{}", "No value of a known type to look up.".yellow());
+        return;
+    }
+    for (atom_type, values) in &values_by_type {
+        info!("{} values looked up as {}", values.len(), atom_type);
+    }
+    let lookup_csv_string = match lookup_values_in_dtl(&values_by_type, environment) {
         Ok(lookup_csv_string) => lookup_csv_string,
         Err(e) => {
             error!("{}", e);
@@ -432,6 +1120,347 @@ fn manage_lookup(
     }
 }
 
+fn report_unknown_values(values: &[InputValue], unknown_output: &Option<PathBuf>) {
+    let unknown_values: Vec<&str> = values
+        .iter()
+        .filter(|value| value.atom_type == AtomType::Unknown)
+        .map(|value| value.value.as_str())
+        .collect();
+    if unknown_values.is_empty() {
+        return;
+    }
+    if let Some(path) = unknown_output {
+        let mut content: String = unknown_values.join("\n");
+        content.push('\n');
+        if let Err(e) = write_file(path, content) {
+            error!("{}", e);
+            return;
+        }
+    }
+    warn_unknown_values(unknown_values.len(), unknown_output);
+}
+
+fn warn_replaced_lines(input: &Path, replaced_lines: &[usize]) {
+    if replaced_lines.is_empty() {
+        return;
+    }
+    let mut lines: Vec<String> = replaced_lines
+        .iter()
+        .take(MAX_REPORTED_LINES)
+        .map(usize::to_string)
+        .collect();
+    if replaced_lines.len() > MAX_REPORTED_LINES {
+        lines.push("...".to_string());
+    }
+    warn!(
+        "{}",
+        format!(
+            "{}: {} lines contain bytes which could not be decoded, the values containing them were dropped: {}. Use `--encoding` if the input is not UTF-8.",
+            input.display(),
+            replaced_lines.len(),
+            lines.join(", ")
+        )
+        .yellow()
+    );
+}
+
+fn warn_unknown_values(nb_unknown_values: usize, unknown_output: &Option<PathBuf>) {
+    if nb_unknown_values == 0 {
+        return;
+    }
+    match unknown_output {
+        Some(path) => warn!(
+            "{}",
+            format!(
+                "{} values of unknown type listed in {}",
+                nb_unknown_values,
+                path.display()
+            )
+            .yellow()
+        ),
+        None => warn!(
+            "{}",
+            format!(
+                "{} values of unknown type, use `--unknown-output` to list them.",
+                nb_unknown_values
+            )
+            .yellow()
+        ),
+    }
+}
+
+fn verify_command(args: &Verify, cli: &Cli) {
+    let bloom_file: BloomFile = match deserialize_bloom_file(&args.bloom) {
+        Ok(bloom_file) => bloom_file,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let source_result = if let Some(queryhash) = &args.queryhash {
+        get_values_from_queryhash(queryhash.clone(), &cli.environment)
+    } else if let Some(input_path) = &args.file {
+        get_values_from_file(input_path, &args.input_args.options()).map(
+            |(values, replaced_lines)| {
+                warn_replaced_lines(input_path, &replaced_lines);
+                values
+            },
+        )
+    } else {
+        error!("Unexpected case");
+        return;
+    };
+    let source: Vec<String> = match source_result {
+        Ok(source) => {
+            report_unknown_values(
+                &classify_values(source.clone()),
+                &args.input_args.unknown_output,
+            );
+            source
+        }
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+
+    let (source, ranges) = split_ip_ranges(source);
+    let mut report: VerifyReport = verify_bloom(&bloom_file.bloom, &source, args.samples);
+    // IP ranges are not stored in the bloom filter itself
+    report.nb_checked += ranges.len();
+    for range in ranges {
+        if !bloom_file.ranges.contains(&range) {
+            report.missing.push(range.to_string());
+        }
+    }
+    info!(
+        "{}",
+        format!(
+            "{} source values checked, {} missing",
+            report.nb_checked,
+            report.missing.len()
+        )
+        .bright_blue()
+        .bold()
+    );
+    match args.rate.or(bloom_file.metadata.rate) {
+        Some(rate) => {
+            info!(
+                "{}",
+                format!(
+                    "{} false positives out of {} samples, observed rate is {} (requested rate is {})",
+                    report.nb_false_positives,
+                    report.nb_samples,
+                    report.observed_rate(),
+                    rate
+                )
+                .bright_blue()
+                .bold()
+            );
+            if report.observed_rate() > rate {
+                warn!(
+                    "{}",
+                    "The observed false positive rate is higher than the requested rate.".yellow()
+                );
+            }
+        }
+        None => {
+            info!(
+                "{}",
+                format!(
+                    "{} false positives out of {} samples, observed rate is {}",
+                    report.nb_false_positives,
+                    report.nb_samples,
+                    report.observed_rate()
+                )
+                .bright_blue()
+                .bold()
+            );
+            warn!(
+                "{}",
+                "The bloom filter does not record its requested false positive rate, use `--rate` to compare the observed rate with it."
+                    .yellow()
+            );
+        }
+    }
+    if !report.missing.is_empty() {
+        for value in &report.missing {
+            println!("{}", value);
+        }
+        error!(
+            "{}",
+            format!(
+                "{} is corrupted: {} values of the source are missing from the bloom filter.",
+                args.bloom.display(),
+                report.missing.len()
+            )
+            .red()
+            .bold()
+        );
+        std::process::exit(1);
+    }
+    info!(
+        "{}{}",
+        "No false negative found in the bloom filter at path: "
+            .green()
+            .bold(),
+        args.bloom.display()
+    );
+}
+
+fn diff_command(args: &Diff) {
+    let old: BloomFile = match deserialize_bloom_file(&args.old) {
+        Ok(old) => old,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let new: BloomFile = match deserialize_bloom_file(&args.new) {
+        Ok(new) => new,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let patch: BloomPatch = match diff_blooms(&old, &new) {
+        Ok(patch) => patch,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let output_path: PathBuf = match &args.output {
+        Some(path) => path.clone(),
+        None => {
+            let mut path = args.new.clone();
+            path.set_extension("patch");
+            path
+        }
+    };
+    info!(
+        "{}",
+        format!("{} bits changed", patch.nb_changed_bits())
+            .bright_blue()
+            .bold()
+    );
+    match write_patch_to_file(&patch, &output_path) {
+        Ok(()) => info!(
+            "{}{}",
+            "Successfully created the patch at path: ".green().bold(),
+            output_path.display()
+        ),
+        Err(e) => error!("{}", e),
+    }
+}
+
+fn patch_command(args: &Patch) {
+    let old: BloomFile = match deserialize_bloom_file(&args.bloom) {
+        Ok(old) => old,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let patch: BloomPatch = match read_patch_file(&args.patch) {
+        Ok(patch) => patch,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    match apply_patch(&old, &patch) {
+        Ok(bloom_file) => {
+            let output_path: &PathBuf = args.output.as_ref().unwrap_or(&args.bloom);
+            write_bloom(&bloom_file, output_path);
+        }
+        Err(e) => error!("{}: {}", args.patch.display(), e),
+    }
+}
+
+fn get_signing_key(key_path: &Option<PathBuf>) -> Result<Option<Vec<u8>>, String> {
+    match key_path {
+        Some(key_path) => read_signing_key(key_path).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn export_bundle_command(args: &ExportBundle) {
+    let key: Option<Vec<u8>> = match get_signing_key(&args.key) {
+        Ok(key) => key,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let mut bloom_paths: Vec<PathBuf> = Vec::new();
+    if let Some(paths) = &args.bloom {
+        bloom_paths.extend(paths.clone());
+    }
+    if let Some(dirs) = &args.bloom_dir {
+        bloom_paths.extend(dirs.clone());
+    }
+    let tags: Vec<String> = args.tag.clone().unwrap_or_default();
+    let resolved_paths: Vec<PathBuf> = match resolve_bloom_paths(&bloom_paths, &tags) {
+        Ok(resolved) => resolved.into_iter().map(|(_, path)| path).collect(),
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    match export_bundle(&resolved_paths, &args.output, key.as_deref()) {
+        Ok(manifest) => info!(
+            "{}{}",
+            format!(
+                "Successfully exported {} bloom filters in the bundle at path: ",
+                manifest.filters.len()
+            )
+            .green()
+            .bold(),
+            args.output.display()
+        ),
+        Err(e) => error!("{}", e),
+    }
+}
+
+fn import_bundle_command(args: &ImportBundle) {
+    let key: Option<Vec<u8>> = match get_signing_key(&args.key) {
+        Ok(key) => key,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    match import_bundle(&args.input, &args.directory, key.as_deref(), args.force) {
+        Ok(manifest) => {
+            if key.is_none() {
+                warn!(
+                    "{}",
+                    "No key provided, the signatures of the bloom filters were not verified."
+                        .yellow()
+                );
+            }
+            for entry in &manifest.filters {
+                info!("{}: {}", entry.name, entry.checksum);
+            }
+            info!(
+                "{}{}",
+                format!(
+                    "Successfully imported {} bloom filters in the directory: ",
+                    manifest.filters.len()
+                )
+                .green()
+                .bold(),
+                args.directory.display()
+            );
+        }
+        Err(e) => error!(
+            "{}",
+            format!("{}: The bundle was rejected: {}", args.input.display(), e).red()
+        ),
+    }
+}
+
 #[test]
 fn test_validate_false_positive_rate() {
     assert!(validate_false_positive("0.0").is_err());
@@ -439,3 +1468,36 @@ fn test_validate_false_positive_rate() {
     assert!(validate_false_positive("2.5").is_err());
     assert!(validate_false_positive("0.0000001").is_ok());
 }
+
+#[test]
+fn test_check_bloom_age() {
+    let now: u64 = 100 * dtl_hunter::SECONDS_PER_DAY;
+    let metadata = BloomMetadata {
+        created_at: Some(now - 40 * dtl_hunter::SECONDS_PER_DAY),
+        expires_at: Some(now - dtl_hunter::SECONDS_PER_DAY),
+        ..Default::default()
+    };
+    assert_eq!(
+        check_bloom_age("a.bloom", &metadata, 30, false, now),
+        Ok("40".to_string())
+    );
+    assert!(check_bloom_age("a.bloom", &metadata, 30, true, now).is_err());
+    assert_eq!(
+        check_bloom_age("a.bloom", &BloomMetadata::default(), 30, true, now),
+        Ok(String::new())
+    );
+}
+
+#[test]
+fn test_extract_conflicts_with_format() {
+    let parse = |args: &[&str]| {
+        Cli::try_parse_from(
+            ["dtl_hunter", "check", "-i", "input.txt", "-b", "a.bloom"]
+                .iter()
+                .chain(args),
+        )
+    };
+    assert!(parse(&["--extract"]).is_ok());
+    assert!(parse(&["--format", "zeek"]).is_ok());
+    assert!(parse(&["--extract", "--format", "pcap"]).is_err());
+}
@@ -0,0 +1,132 @@
+use crate::{bloom_checksum, BloomFile, BloomMetadata};
+use bloomfilter::Bloom;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BloomPatch {
+    pub old_checksum: String,
+    pub new_checksum: String,
+    pub metadata: BloomMetadata,
+    // positions of the bits that changed, each one stored as the gap from the previous one
+    pub changed_bits: Vec<u64>,
+    // the IP ranges are small enough to be sent whole
+    #[serde(default)]
+    pub ranges: Vec<IpNet>,
+}
+
+impl BloomPatch {
+    pub fn nb_changed_bits(&self) -> usize {
+        self.changed_bits.len()
+    }
+}
+
+pub fn diff_blooms(old: &BloomFile, new: &BloomFile) -> Result<BloomPatch, String> {
+    if old.bloom.number_of_bits() != new.bloom.number_of_bits()
+        || old.bloom.number_of_hash_functions() != new.bloom.number_of_hash_functions()
+        || old.bloom.sip_keys() != new.bloom.sip_keys()
+    {
+        return Err("The bloom filters were not created with the same parameters, use the same `--seed` and `--capacity` when creating them".to_string());
+    }
+    let old_bitmap: Vec<u8> = old.bloom.bitmap();
+    let new_bitmap: Vec<u8> = new.bloom.bitmap();
+    let mut changed_bits: Vec<u64> = Vec::new();
+    let mut previous: u64 = 0;
+    for (index, (old_byte, new_byte)) in old_bitmap.iter().zip(new_bitmap.iter()).enumerate() {
+        let changed: u8 = old_byte ^ new_byte;
+        if changed == 0 {
+            continue;
+        }
+        for bit in 0..8 {
+            // bitmaps store the first bit of each byte in its most significant bit
+            if changed & (0x80 >> bit) != 0 {
+                let position: u64 = index as u64 * 8 + bit;
+                changed_bits.push(position - previous);
+                previous = position;
+            }
+        }
+    }
+    Ok(BloomPatch {
+        old_checksum: bloom_checksum(&old.bloom),
+        new_checksum: bloom_checksum(&new.bloom),
+        metadata: new.metadata.clone(),
+        changed_bits,
+        ranges: new.ranges.clone(),
+    })
+}
+
+pub fn apply_patch(old: &BloomFile, patch: &BloomPatch) -> Result<BloomFile, String> {
+    if bloom_checksum(&old.bloom) != patch.old_checksum {
+        return Err("The patch was not created from this bloom filter".to_string());
+    }
+    let mut bitmap: Vec<u8> = old.bloom.bitmap();
+    let mut position: u64 = 0;
+    for gap in &patch.changed_bits {
+        // the gaps come from the patch file, which may have been crafted
+        position = match position.checked_add(*gap) {
+            Some(position) => position,
+            None => return Err("The patch is invalid, its bit positions overflow".to_string()),
+        };
+        let index: usize = (position / 8) as usize;
+        if index >= bitmap.len() {
+            return Err("The patch changes bits outside of the bloom filter".to_string());
+        }
+        bitmap[index] ^= 0x80 >> (position % 8);
+    }
+    let bloom: Bloom<String> = Bloom::from_existing(
+        &bitmap,
+        old.bloom.number_of_bits(),
+        old.bloom.number_of_hash_functions(),
+        old.bloom.sip_keys(),
+    );
+    if bloom_checksum(&bloom) != patch.new_checksum {
+        return Err(
+            "The patched bloom filter does not match the checksum of the patch".to_string(),
+        );
+    }
+    Ok(BloomFile {
+        metadata: patch.metadata.clone(),
+        bloom,
+        ranges: patch.ranges.clone(),
+    })
+}
+
+pub fn write_patch_to_file(patch: &BloomPatch, output_path: &PathBuf) -> Result<(), String> {
+    let serialized: String = ron::to_string(patch).expect("Failed to serialize the patch");
+    let output_file: File = match File::create(output_path) {
+        Ok(output_file) => output_file,
+        Err(e) => return Err(format!("{}: {}", output_path.display(), e)),
+    };
+    let mut encoder = GzEncoder::new(output_file, Compression::best());
+    match encoder
+        .write_all(serialized.as_bytes())
+        .and_then(|()| encoder.finish().map(|_| ()))
+    {
+        Ok(()) => Ok(()),
+        Err(e) => Err(format!("{}: {}", output_path.display(), e)),
+    }
+}
+
+pub fn read_patch_file(path: &PathBuf) -> Result<BloomPatch, String> {
+    let file: File = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    let mut ron_string = String::new();
+    if let Err(e) = GzDecoder::new(file).read_to_string(&mut ron_string) {
+        return Err(format!("{}: {}", path.display(), e));
+    }
+    match ron::from_str(&ron_string) {
+        Ok(patch) => Ok(patch),
+        Err(_) => Err(format!(
+            "Failed to deserialize patch located in {}",
+            path.display()
+        )),
+    }
+}
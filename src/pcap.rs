@@ -0,0 +1,806 @@
+use crate::InputValue;
+use std::collections::HashSet;
+use std::io::{self, BufRead};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const PCAPNG_SECTION_HEADER: [u8; 4] = [0x0a, 0x0d, 0x0d, 0x0a];
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_SIMPLE_PACKET: u32 = 3;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+
+// larger records are taken for a corrupted file rather than allocated
+const MAX_RECORD_LEN: usize = 256 * 1024 * 1024;
+
+const HTTP_METHODS: [&str; 9] = [
+    "GET", "POST", "HEAD", "PUT", "DELETE", "OPTIONS", "CONNECT", "PATCH", "TRACE",
+];
+
+#[derive(Clone, Copy)]
+struct ByteOrder {
+    little_endian: bool,
+}
+
+impl ByteOrder {
+    fn u16(&self, bytes: &[u8]) -> u16 {
+        let bytes: [u8; 2] = [bytes[0], bytes[1]];
+        if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        }
+    }
+
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes: [u8; 4] = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        }
+    }
+}
+
+// timestamps are counted in units of 10^-n or 2^-n seconds
+#[derive(Clone, Copy)]
+enum TimestampResolution {
+    Decimal(u32),
+    Binary(u32),
+}
+
+fn format_timestamp(units: u64, resolution: TimestampResolution) -> String {
+    match resolution {
+        TimestampResolution::Decimal(0) => units.to_string(),
+        TimestampResolution::Decimal(digits) => {
+            let divisor: u64 = 10u64.pow(digits.min(19));
+            format!(
+                "{}.{:0width$}",
+                units / divisor,
+                units % divisor,
+                width = digits as usize
+            )
+        }
+        TimestampResolution::Binary(bits) => {
+            format!("{:.6}", units as f64 / 2f64.powi(bits as i32))
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Flow {
+    protocol: u8,
+    src: IpAddr,
+    src_port: Option<u16>,
+    dst: IpAddr,
+    dst_port: Option<u16>,
+}
+
+impl Flow {
+    fn context(&self, timestamp: &str) -> Vec<(String, String)> {
+        let protocol: String = match self.protocol {
+            1 => "icmp".to_string(),
+            6 => "tcp".to_string(),
+            17 => "udp".to_string(),
+            58 => "icmpv6".to_string(),
+            protocol => protocol.to_string(),
+        };
+        vec![
+            ("ts".to_string(), timestamp.to_string()),
+            ("proto".to_string(), protocol),
+            ("src".to_string(), socket_address(self.src, self.src_port)),
+            ("dst".to_string(), socket_address(self.dst, self.dst_port)),
+        ]
+    }
+}
+
+fn socket_address(ip: IpAddr, port: Option<u16>) -> String {
+    match (ip, port) {
+        (IpAddr::V4(ip), Some(port)) => format!("{}:{}", ip, port),
+        (IpAddr::V6(ip), Some(port)) => format!("[{}]:{}", ip, port),
+        (ip, None) => ip.to_string(),
+    }
+}
+
+// reads the packets of pcap and pcapng files, extracting their IPs, DNS names, HTTP hosts and
+// TLS server names
+pub fn read_pcap<F: FnMut(InputValue)>(
+    mut reader: impl BufRead,
+    callback: F,
+) -> Result<(), io::Error> {
+    let mut magic: [u8; 4] = [0; 4];
+    reader.read_exact(&mut magic)?;
+    let mut extractor = PacketExtractor {
+        seen_flows: HashSet::new(),
+        callback,
+    };
+    let resolution = |little_endian: bool, nanoseconds: bool| {
+        let resolution = TimestampResolution::Decimal(if nanoseconds { 9 } else { 6 });
+        (ByteOrder { little_endian }, resolution)
+    };
+    match magic {
+        [0xd4, 0xc3, 0xb2, 0xa1] => {
+            read_pcap_records(reader, resolution(true, false), &mut extractor)
+        }
+        [0xa1, 0xb2, 0xc3, 0xd4] => {
+            read_pcap_records(reader, resolution(false, false), &mut extractor)
+        }
+        [0x4d, 0x3c, 0xb2, 0xa1] => {
+            read_pcap_records(reader, resolution(true, true), &mut extractor)
+        }
+        [0xa1, 0xb2, 0x3c, 0x4d] => {
+            read_pcap_records(reader, resolution(false, true), &mut extractor)
+        }
+        PCAPNG_SECTION_HEADER => read_pcapng_blocks(reader, &mut extractor),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Not a pcap or pcapng file",
+        )),
+    }
+}
+
+// reads until the buffer is full or the end of the input, a truncated last record being
+// common in captures that were interrupted
+fn read_full(reader: &mut impl BufRead, buffer: &mut [u8]) -> Result<bool, io::Error> {
+    let mut read: usize = 0;
+    while read < buffer.len() {
+        match reader.read(&mut buffer[read..]) {
+            Ok(0) => return Ok(false),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+fn read_record(reader: &mut impl BufRead, len: usize) -> Result<Option<Vec<u8>>, io::Error> {
+    if len > MAX_RECORD_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Record of {} bytes in the capture", len),
+        ));
+    }
+    let mut record: Vec<u8> = vec![0; len];
+    Ok(read_full(reader, &mut record)?.then_some(record))
+}
+
+fn read_pcap_records<F: FnMut(InputValue)>(
+    mut reader: impl BufRead,
+    (byte_order, resolution): (ByteOrder, TimestampResolution),
+    extractor: &mut PacketExtractor<F>,
+) -> Result<(), io::Error> {
+    let mut header: [u8; 20] = [0; 20];
+    reader.read_exact(&mut header)?;
+    let linktype: u32 = byte_order.u32(&header[16..20]) & 0x0fff_ffff;
+    let mut record_header: [u8; 16] = [0; 16];
+    while read_full(&mut reader, &mut record_header)? {
+        let seconds: u64 = byte_order.u32(&record_header[0..4]) as u64;
+        let fraction: u64 = byte_order.u32(&record_header[4..8]) as u64;
+        let captured_len: usize = byte_order.u32(&record_header[8..12]) as usize;
+        let packet: Vec<u8> = match read_record(&mut reader, captured_len)? {
+            Some(packet) => packet,
+            None => break,
+        };
+        let units: u64 = match resolution {
+            TimestampResolution::Decimal(digits) => seconds * 10u64.pow(digits) + fraction,
+            TimestampResolution::Binary(_) => seconds,
+        };
+        extractor.extract(linktype, &packet, &format_timestamp(units, resolution));
+    }
+    Ok(())
+}
+
+struct Interface {
+    linktype: u32,
+    resolution: TimestampResolution,
+}
+
+fn read_pcapng_blocks<F: FnMut(InputValue)>(
+    mut reader: impl BufRead,
+    extractor: &mut PacketExtractor<F>,
+) -> Result<(), io::Error> {
+    let mut byte_order = ByteOrder {
+        little_endian: true,
+    };
+    let mut interfaces: Vec<Interface> = Vec::new();
+    // the block type of the first section header was read to detect the format
+    let mut block_header: [u8; 8] = [0; 8];
+    block_header[..4].copy_from_slice(&PCAPNG_SECTION_HEADER);
+    if !read_full(&mut reader, &mut block_header[4..])? {
+        return Ok(());
+    }
+    loop {
+        let body: Vec<u8> = if block_header[..4] == PCAPNG_SECTION_HEADER {
+            // the byte order of the section is given by the magic following the block length
+            let mut magic: [u8; 4] = [0; 4];
+            if !read_full(&mut reader, &mut magic)? {
+                break;
+            }
+            byte_order = match magic {
+                [0x4d, 0x3c, 0x2b, 0x1a] => ByteOrder {
+                    little_endian: true,
+                },
+                [0x1a, 0x2b, 0x3c, 0x4d] => ByteOrder {
+                    little_endian: false,
+                },
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Invalid pcapng section header",
+                    ))
+                }
+            };
+            interfaces.clear();
+            let block_len: usize = byte_order.u32(&block_header[4..8]) as usize;
+            match read_record(&mut reader, block_len.saturating_sub(12))? {
+                Some(_) => Vec::new(),
+                None => break,
+            }
+        } else {
+            let block_len: usize = byte_order.u32(&block_header[4..8]) as usize;
+            match read_record(&mut reader, block_len.saturating_sub(8))? {
+                Some(body) => body,
+                None => break,
+            }
+        };
+        match byte_order.u32(&block_header[..4]) {
+            PCAPNG_INTERFACE_DESCRIPTION if body.len() >= 8 => {
+                interfaces.push(parse_interface(&body, byte_order))
+            }
+            PCAPNG_ENHANCED_PACKET if body.len() >= 20 => {
+                let interface: Option<&Interface> =
+                    interfaces.get(byte_order.u32(&body[0..4]) as usize);
+                let units: u64 = ((byte_order.u32(&body[4..8]) as u64) << 32)
+                    | byte_order.u32(&body[8..12]) as u64;
+                let captured_len: usize = byte_order.u32(&body[12..16]) as usize;
+                if let (Some(interface), Some(packet)) =
+                    (interface, body.get(20..20 + captured_len))
+                {
+                    let timestamp: String = format_timestamp(units, interface.resolution);
+                    extractor.extract(interface.linktype, packet, &timestamp);
+                }
+            }
+            // simple packets have no timestamp and come from the first interface
+            PCAPNG_SIMPLE_PACKET if body.len() >= 8 => {
+                if let Some(interface) = interfaces.first() {
+                    extractor.extract(interface.linktype, &body[4..body.len() - 4], "");
+                }
+            }
+            _ => (),
+        }
+        if !read_full(&mut reader, &mut block_header)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn parse_interface(body: &[u8], byte_order: ByteOrder) -> Interface {
+    let mut interface = Interface {
+        linktype: byte_order.u16(&body[0..2]) as u32,
+        resolution: TimestampResolution::Decimal(6),
+    };
+    let mut options: &[u8] = &body[8..];
+    while options.len() >= 4 {
+        let code: u16 = byte_order.u16(&options[0..2]);
+        let len: usize = byte_order.u16(&options[2..4]) as usize;
+        let value: &[u8] = match options.get(4..4 + len) {
+            Some(value) => value,
+            None => break,
+        };
+        match (code, value) {
+            (0, _) => break,
+            // if_tsresol, a power of 2 when its most significant bit is set
+            (9, [resolution]) if resolution & 0x80 != 0 => {
+                interface.resolution = TimestampResolution::Binary((resolution & 0x7f) as u32)
+            }
+            (9, [resolution]) => {
+                interface.resolution = TimestampResolution::Decimal(*resolution as u32)
+            }
+            _ => (),
+        }
+        options = options.get(4 + len.div_ceil(4) * 4..).unwrap_or_default();
+    }
+    interface
+}
+
+struct PacketExtractor<F: FnMut(InputValue)> {
+    // the IPs of a flow are only extracted from its first packet
+    seen_flows: HashSet<Flow>,
+    callback: F,
+}
+
+impl<F: FnMut(InputValue)> PacketExtractor<F> {
+    fn extract(&mut self, linktype: u32, packet: &[u8], timestamp: &str) {
+        let (flow, payload) = match network_layer(linktype, packet).and_then(parse_ip) {
+            Some(parsed) => parsed,
+            None => return,
+        };
+        let mut values: Vec<(&str, String)> = Vec::new();
+        if self.seen_flows.insert(flow) {
+            values.push(("src_ip", flow.src.to_string()));
+            values.push(("dst_ip", flow.dst.to_string()));
+        }
+        let ports: [Option<u16>; 2] = [flow.src_port, flow.dst_port];
+        match flow.protocol {
+            17 if ports
+                .iter()
+                .any(|port| matches!(port, Some(53 | 5353 | 5355))) =>
+            {
+                values.extend(parse_dns(payload))
+            }
+            6 if ports.contains(&Some(53)) => {
+                values.extend(payload.get(2..).map(parse_dns).unwrap_or_default())
+            }
+            6 => {
+                if let Some(host) = parse_http_host(payload) {
+                    values.push(("http.host", host));
+                }
+                if let Some(server_name) = parse_tls_server_name(payload) {
+                    values.push(("tls.sni", server_name));
+                }
+            }
+            _ => (),
+        }
+        let mut seen_values: HashSet<String> = HashSet::new();
+        for (field, value) in values {
+            if value.is_empty() || !seen_values.insert(value.clone()) {
+                continue;
+            }
+            let mut context: Vec<(String, String)> = flow.context(timestamp);
+            context.push(("field".to_string(), field.to_string()));
+            (self.callback)(InputValue::with_context(value, context));
+        }
+    }
+}
+
+// the IP packet carried by the link layer frame
+fn network_layer(linktype: u32, frame: &[u8]) -> Option<&[u8]> {
+    let (ethertype, packet): (Option<u16>, &[u8]) = match linktype {
+        LINKTYPE_ETHERNET => {
+            let mut ethertype: u16 = u16::from_be_bytes([*frame.get(12)?, *frame.get(13)?]);
+            let mut packet: &[u8] = frame.get(14..)?;
+            // 802.1Q and 802.1ad VLAN tags
+            while ethertype == 0x8100 || ethertype == 0x88a8 {
+                ethertype = u16::from_be_bytes([*packet.get(2)?, *packet.get(3)?]);
+                packet = packet.get(4..)?;
+            }
+            (Some(ethertype), packet)
+        }
+        LINKTYPE_LINUX_SLL => (
+            Some(u16::from_be_bytes([*frame.get(14)?, *frame.get(15)?])),
+            frame.get(16..)?,
+        ),
+        LINKTYPE_LINUX_SLL2 => (
+            Some(u16::from_be_bytes([*frame.first()?, *frame.get(1)?])),
+            frame.get(20..)?,
+        ),
+        // the address family in the byte order of the host which captured the packets
+        LINKTYPE_NULL => (None, frame.get(4..)?),
+        // raw IP packets, the version being given by the packet itself
+        12 | 14 | 101 | 228 | 229 => (None, frame),
+        _ => return None,
+    };
+    match ethertype {
+        None | Some(0x0800) | Some(0x86dd) => Some(packet),
+        Some(_) => None,
+    }
+}
+
+// the flow and the transport payload of the packet, empty for non-first fragments
+fn parse_ip(packet: &[u8]) -> Option<(Flow, &[u8])> {
+    let (protocol, src, dst, mut transport, first_fragment): (u8, IpAddr, IpAddr, &[u8], bool) =
+        match packet.first()? >> 4 {
+            4 => {
+                let header_len: usize = (packet[0] & 0x0f) as usize * 4;
+                // corrupt IHL, or header options cut by the snapshot length
+                if header_len < 20 || header_len > packet.len() {
+                    return None;
+                }
+                let total_len: usize =
+                    u16::from_be_bytes([*packet.get(2)?, *packet.get(3)?]) as usize;
+                let fragment_offset: u16 =
+                    u16::from_be_bytes([*packet.get(6)?, *packet.get(7)?]) & 0x1fff;
+                let src: [u8; 4] = packet.get(12..16)?.try_into().ok()?;
+                let dst: [u8; 4] = packet.get(16..20)?.try_into().ok()?;
+                let end: usize = total_len.clamp(header_len, packet.len());
+                (
+                    packet[9],
+                    IpAddr::V4(Ipv4Addr::from(src)),
+                    IpAddr::V4(Ipv4Addr::from(dst)),
+                    packet.get(header_len..end)?,
+                    fragment_offset == 0,
+                )
+            }
+            6 => {
+                let src: [u8; 16] = packet.get(8..24)?.try_into().ok()?;
+                let dst: [u8; 16] = packet.get(24..40)?.try_into().ok()?;
+                let mut next_header: u8 = *packet.get(6)?;
+                let mut payload: &[u8] = packet.get(40..)?;
+                let mut first_fragment: bool = true;
+                // extension headers: hop-by-hop, routing, fragment, authentication and
+                // destination options
+                loop {
+                    let header_len: usize = match next_header {
+                        0 | 43 | 60 => (*payload.get(1)? as usize + 1) * 8,
+                        44 => {
+                            first_fragment =
+                                u16::from_be_bytes([*payload.get(2)?, *payload.get(3)?]) & 0xfff8
+                                    == 0;
+                            8
+                        }
+                        51 => (*payload.get(1)? as usize + 2) * 4,
+                        _ => break,
+                    };
+                    next_header = *payload.first()?;
+                    payload = payload.get(header_len..)?;
+                }
+                (
+                    next_header,
+                    IpAddr::V6(Ipv6Addr::from(src)),
+                    IpAddr::V6(Ipv6Addr::from(dst)),
+                    payload,
+                    first_fragment,
+                )
+            }
+            _ => return None,
+        };
+    let mut flow = Flow {
+        protocol,
+        src,
+        src_port: None,
+        dst,
+        dst_port: None,
+    };
+    if !first_fragment {
+        return Some((flow, &[]));
+    }
+    if protocol == 6 || protocol == 17 {
+        flow.src_port = Some(u16::from_be_bytes([
+            *transport.first()?,
+            *transport.get(1)?,
+        ]));
+        flow.dst_port = Some(u16::from_be_bytes([*transport.get(2)?, *transport.get(3)?]));
+        let header_len: usize = if protocol == 6 {
+            (*transport.get(12)? >> 4) as usize * 4
+        } else {
+            8
+        };
+        transport = transport.get(header_len..).unwrap_or_default();
+    }
+    Some((flow, transport))
+}
+
+// the names of the questions and answers of a DNS message, and the addresses of its answers
+fn parse_dns(message: &[u8]) -> Vec<(&'static str, String)> {
+    let mut values: Vec<(&'static str, String)> = Vec::new();
+    let count = |offset: usize| {
+        message
+            .get(offset..offset + 2)
+            .map_or(0, |count| u16::from_be_bytes([count[0], count[1]]))
+    };
+    let (question_count, answer_count): (u16, u16) = (count(4), count(6));
+    let mut position: usize = 12;
+    for _ in 0..question_count {
+        match read_dns_name(message, &mut position) {
+            Some(name) => values.push(("dns.query", name)),
+            None => return values,
+        }
+        position += 4;
+    }
+    for _ in 0..answer_count {
+        let name: String = match read_dns_name(message, &mut position) {
+            Some(name) => name,
+            None => return values,
+        };
+        values.push(("dns.answer", name));
+        let record: &[u8] = match message.get(position..position + 10) {
+            Some(record) => record,
+            None => return values,
+        };
+        let record_type: u16 = u16::from_be_bytes([record[0], record[1]]);
+        let data_len: usize = u16::from_be_bytes([record[8], record[9]]) as usize;
+        let data_position: usize = position + 10;
+        let data: &[u8] = match message.get(data_position..data_position + data_len) {
+            Some(data) => data,
+            None => return values,
+        };
+        match (record_type, data_len) {
+            (1, 4) => values.push((
+                "dns.answer",
+                Ipv4Addr::new(data[0], data[1], data[2], data[3]).to_string(),
+            )),
+            (28, 16) => {
+                let address: [u8; 16] = data.try_into().unwrap_or_default();
+                values.push(("dns.answer", Ipv6Addr::from(address).to_string()))
+            }
+            // CNAME
+            (5, _) => {
+                if let Some(name) = read_dns_name(message, &mut data_position.clone()) {
+                    values.push(("dns.answer", name))
+                }
+            }
+            _ => (),
+        }
+        position = data_position + data_len;
+    }
+    values
+}
+
+fn read_dns_name(message: &[u8], position: &mut usize) -> Option<String> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut offset: usize = *position;
+    let mut jumped: bool = false;
+    // compression pointers always point backwards in valid messages
+    for _ in 0..128 {
+        let len: u8 = *message.get(offset)?;
+        match len {
+            0 => {
+                if !jumped {
+                    *position = offset + 1;
+                }
+                return Some(labels.join("."));
+            }
+            len if len & 0xc0 == 0xc0 => {
+                let pointer: usize =
+                    (((len & 0x3f) as usize) << 8) | *message.get(offset + 1)? as usize;
+                if !jumped {
+                    *position = offset + 2;
+                    jumped = true;
+                }
+                offset = pointer;
+            }
+            len if len & 0xc0 == 0 => {
+                let label: &[u8] = message.get(offset + 1..offset + 1 + len as usize)?;
+                labels.push(String::from_utf8_lossy(label).to_string());
+                offset += 1 + len as usize;
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+// the Host header of an HTTP request starting in the payload
+fn parse_http_host(payload: &[u8]) -> Option<String> {
+    let method: &[u8] = &payload[..payload.iter().take(8).position(|byte| *byte == b' ')?];
+    if !HTTP_METHODS.iter().any(|known| known.as_bytes() == method) {
+        return None;
+    }
+    let headers_end: usize = payload
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .unwrap_or(payload.len());
+    let headers: String = String::from_utf8_lossy(&payload[..headers_end]).to_string();
+    let host: &str = headers.split("\r\n").skip(1).find_map(|header| {
+        let (name, value) = header.split_once(':')?;
+        name.trim()
+            .eq_ignore_ascii_case("host")
+            .then_some(value.trim())
+    })?;
+    let host: &str = match host.strip_prefix('[') {
+        Some(bracketed) => &bracketed[..bracketed.find(']')?],
+        None => match host.rsplit_once(':') {
+            Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+            _ => host,
+        },
+    };
+    Some(host.to_string())
+}
+
+// the server name extension of a TLS ClientHello starting in the payload
+fn parse_tls_server_name(payload: &[u8]) -> Option<String> {
+    // handshake record of a ClientHello
+    if payload.len() < 9 || payload[0] != 0x16 || payload[1] != 3 || payload[5] != 1 {
+        return None;
+    }
+    let hello: &[u8] = &payload[9..];
+    let read_len = |position: usize, size: usize| -> Option<usize> {
+        let bytes: &[u8] = hello.get(position..position + size)?;
+        Some(bytes.iter().fold(0, |len, byte| len << 8 | *byte as usize))
+    };
+    // version and random
+    let mut position: usize = 2 + 32;
+    position += 1 + read_len(position, 1)?; // session id
+    position += 2 + read_len(position, 2)?; // cipher suites
+    position += 1 + read_len(position, 1)?; // compression methods
+    let extensions_end: usize = position + 2 + read_len(position, 2)?;
+    position += 2;
+    while position + 4 <= extensions_end {
+        let extension_type: usize = read_len(position, 2)?;
+        let extension_len: usize = read_len(position + 2, 2)?;
+        position += 4;
+        if extension_type == 0 {
+            // server name list, with the type and length of the first name
+            let name_len: usize = read_len(position + 3, 2)?;
+            let name: &[u8] = hello.get(position + 5..position + 5 + name_len)?;
+            return Some(String::from_utf8_lossy(name).to_string());
+        }
+        position += extension_len;
+    }
+    None
+}
+
+#[cfg(test)]
+fn ipv4_packet(protocol: u8, src: [u8; 4], dst: [u8; 4], transport: &[u8]) -> Vec<u8> {
+    let mut packet: Vec<u8> = vec![0x45, 0, 0, 0, 0, 0, 0x40, 0, 64, protocol, 0, 0];
+    packet[2..4].copy_from_slice(&((20 + transport.len()) as u16).to_be_bytes());
+    packet.extend(src);
+    packet.extend(dst);
+    packet.extend(transport);
+    packet
+}
+
+#[cfg(test)]
+fn transport_segment(tcp: bool, src_port: u16, dst_port: u16, payload: &[u8]) -> Vec<u8> {
+    let mut segment: Vec<u8> = Vec::new();
+    segment.extend(src_port.to_be_bytes());
+    segment.extend(dst_port.to_be_bytes());
+    if tcp {
+        segment.extend([0, 0, 0, 1, 0, 0, 0, 0, 0x50, 0x18, 0xff, 0xff, 0, 0, 0, 0]);
+    } else {
+        segment.extend(((8 + payload.len()) as u16).to_be_bytes());
+        segment.extend([0, 0]);
+    }
+    segment.extend(payload);
+    segment
+}
+
+#[test]
+fn test_read_pcap() {
+    let read = |capture: &[u8]| {
+        let mut values: Vec<(String, String)> = Vec::new();
+        read_pcap(capture, |value| {
+            values.push((value.value.clone(), value.context_string()))
+        })
+        .unwrap();
+        values
+    };
+
+    // a DNS query for evil.com in an Ethernet frame
+    let mut dns_query: Vec<u8> = vec![0x12, 0x34, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    dns_query.extend(b"\x04evil\x03com\x00\x00\x01\x00\x01");
+    let mut frame: Vec<u8> = vec![0; 12];
+    frame.extend([0x08, 0x00]);
+    frame.extend(ipv4_packet(
+        17,
+        [10, 0, 0, 9],
+        [8, 8, 8, 8],
+        &transport_segment(false, 40000, 53, &dns_query),
+    ));
+    let mut pcap: Vec<u8> = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+    pcap.extend([0; 8]);
+    pcap.extend(65535u32.to_le_bytes());
+    pcap.extend(1u32.to_le_bytes());
+    pcap.extend(1591367999u32.to_le_bytes());
+    pcap.extend(305988u32.to_le_bytes());
+    pcap.extend((frame.len() as u32).to_le_bytes());
+    pcap.extend((frame.len() as u32).to_le_bytes());
+    pcap.extend(&frame);
+    let context = |field: &str| {
+        format!(
+            "ts=1591367999.305988;proto=udp;src=10.0.0.9:40000;dst=8.8.8.8:53;field={}",
+            field
+        )
+    };
+    assert_eq!(
+        read(&pcap),
+        vec![
+            ("10.0.0.9".to_string(), context("src_ip")),
+            ("8.8.8.8".to_string(), context("dst_ip")),
+            ("evil.com".to_string(), context("dns.query")),
+        ]
+    );
+
+    // an HTTP request and a TLS ClientHello in raw IP packets of a big endian pcapng file
+    let http_request: &[u8] = b"GET /a.php HTTP/1.1\r\nHost: bad.org:8080\r\nAccept: */*\r\n\r\n";
+    let mut client_hello: Vec<u8> = vec![3, 3];
+    client_hello.extend([0; 32]);
+    client_hello.extend([0, 0, 2, 0x13, 0x01, 1, 0]);
+    let server_name: &[u8] = b"sni.evil.com";
+    let mut extension: Vec<u8> = vec![0, 0];
+    extension.extend(((server_name.len() + 5) as u16).to_be_bytes());
+    extension.extend(((server_name.len() + 3) as u16).to_be_bytes());
+    extension.push(0);
+    extension.extend((server_name.len() as u16).to_be_bytes());
+    extension.extend(server_name);
+    client_hello.extend((extension.len() as u16).to_be_bytes());
+    client_hello.extend(extension);
+    let mut tls_record: Vec<u8> = vec![0x16, 3, 1];
+    tls_record.extend(((client_hello.len() + 4) as u16).to_be_bytes());
+    tls_record.extend([1, 0]);
+    tls_record.extend((client_hello.len() as u16).to_be_bytes());
+    tls_record.extend(client_hello);
+
+    let block = |block_type: u32, body: &[u8]| {
+        let mut block: Vec<u8> = block_type.to_be_bytes().to_vec();
+        let padded_len: usize = body.len().div_ceil(4) * 4;
+        block.extend(((padded_len + 12) as u32).to_be_bytes());
+        block.extend(body);
+        block.extend(vec![0; padded_len - body.len()]);
+        block.extend(((padded_len + 12) as u32).to_be_bytes());
+        block
+    };
+    let packet_block = |timestamp: u64, packet: &[u8]| {
+        let mut body: Vec<u8> = vec![0; 4];
+        body.extend(((timestamp >> 32) as u32).to_be_bytes());
+        body.extend((timestamp as u32).to_be_bytes());
+        body.extend((packet.len() as u32).to_be_bytes());
+        body.extend((packet.len() as u32).to_be_bytes());
+        body.extend(packet);
+        block(PCAPNG_ENHANCED_PACKET, &body)
+    };
+    let mut pcapng: Vec<u8> = block(
+        u32::from_be_bytes(PCAPNG_SECTION_HEADER),
+        &[
+            0x1a, 0x2b, 0x3c, 0x4d, 0, 1, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        ],
+    );
+    // raw IP interface with a resolution of milliseconds
+    pcapng.extend(block(
+        PCAPNG_INTERFACE_DESCRIPTION,
+        &[
+            0, 101, 0, 0, 0, 0, 0xff, 0xff, 0, 9, 0, 1, 3, 0, 0, 0, 0, 0, 0, 0,
+        ],
+    ));
+    pcapng.extend(packet_block(
+        1591367999001,
+        &ipv4_packet(
+            6,
+            [10, 0, 0, 9],
+            [1, 2, 3, 4],
+            &transport_segment(true, 49822, 8080, http_request),
+        ),
+    ));
+    pcapng.extend(packet_block(
+        1591367999002,
+        &ipv4_packet(
+            6,
+            [10, 0, 0, 9],
+            [1, 2, 3, 4],
+            &transport_segment(true, 49822, 8080, &tls_record),
+        ),
+    ));
+    let context = |ts: &str, field: &str| {
+        format!(
+            "ts={};proto=tcp;src=10.0.0.9:49822;dst=1.2.3.4:8080;field={}",
+            ts, field
+        )
+    };
+    assert_eq!(
+        read(&pcapng),
+        vec![
+            ("10.0.0.9".to_string(), context("1591367999.001", "src_ip")),
+            ("1.2.3.4".to_string(), context("1591367999.001", "dst_ip")),
+            (
+                "bad.org".to_string(),
+                context("1591367999.001", "http.host")
+            ),
+            (
+                "sni.evil.com".to_string(),
+                context("1591367999.002", "tls.sni")
+            ),
+        ]
+    );
+
+    assert!(read_pcap(b"evil.com\n".as_slice(), |_| ()).is_err());
+}
+
+#[test]
+fn test_parse_truncated_ipv4() {
+    let packet: Vec<u8> = ipv4_packet(17, [10, 0, 0, 9], [8, 8, 8, 8], &[0; 8]);
+    assert!(parse_ip(&packet).is_some());
+    // IP options cut by the snapshot length
+    let mut truncated: Vec<u8> = packet.clone();
+    truncated[0] = 0x4f;
+    truncated.truncate(24);
+    assert!(parse_ip(&truncated).is_none());
+    // IHL below the minimum header length
+    let mut corrupt: Vec<u8> = packet;
+    corrupt[0] = 0x42;
+    assert!(parse_ip(&corrupt).is_none());
+}
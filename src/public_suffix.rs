@@ -0,0 +1,75 @@
+use publicsuffix::{List, Psl};
+use std::sync::OnceLock;
+
+// copy of https://publicsuffix.org/list/public_suffix_list.dat, to be updated from time to time
+const PUBLIC_SUFFIX_LIST: &[u8] = include_bytes!("../resource/public_suffix_list.dat");
+
+fn public_suffix_list() -> &'static List {
+    static LIST: OnceLock<List> = OnceLock::new();
+    LIST.get_or_init(|| {
+        List::from_bytes(PUBLIC_SUFFIX_LIST).expect("Failed to parse the public suffix list")
+    })
+}
+
+// the domain registered under a public suffix, e.g. evil.co.uk for cdn.evil.co.uk,
+// the domain must be canonicalized
+pub fn registered_domain(domain: &str) -> Option<String> {
+    public_suffix_list()
+        .domain(domain.as_bytes())
+        .map(|registered| String::from_utf8_lossy(registered.as_bytes()).to_string())
+}
+
+// whether the domain ends with a suffix of the list, the domains under unknown suffixes such
+// as file extensions having a registered domain too, the domain must be canonicalized
+pub fn has_known_suffix(domain: &str) -> bool {
+    public_suffix_list()
+        .suffix(domain.as_bytes())
+        .is_some_and(|suffix| suffix.is_known())
+}
+
+// parents of the domain from the closest one up to its registered domain
+pub fn parent_domains(domain: &str) -> Vec<String> {
+    let registered: String = match registered_domain(domain) {
+        Some(registered) => registered,
+        None => return Vec::new(),
+    };
+    let mut parents: Vec<String> = Vec::new();
+    let mut current: &str = domain;
+    while current.len() > registered.len() {
+        current = match current.split_once('.') {
+            Some((_, parent)) => parent,
+            None => break,
+        };
+        parents.push(current.to_string());
+    }
+    parents
+}
+
+#[test]
+fn test_registered_domain() {
+    assert_eq!(
+        registered_domain("cdn.login.evil.com"),
+        Some("evil.com".to_string())
+    );
+    assert_eq!(
+        registered_domain("www.evil.co.uk"),
+        Some("evil.co.uk".to_string())
+    );
+    assert_eq!(registered_domain("co.uk"), None);
+    assert!(has_known_suffix("evil.co.uk"));
+    assert!(!has_known_suffix("report.pdf"));
+}
+
+#[test]
+fn test_parent_domains() {
+    assert_eq!(
+        parent_domains("cdn.login.evil.com"),
+        vec!["login.evil.com".to_string(), "evil.com".to_string()]
+    );
+    assert_eq!(
+        parent_domains("a.evil.co.uk"),
+        vec!["evil.co.uk".to_string()]
+    );
+    assert!(parent_domains("evil.com").is_empty());
+    assert!(parent_domains("co.uk").is_empty());
+}
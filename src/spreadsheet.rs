@@ -0,0 +1,326 @@
+use crate::defang::refang;
+use crate::{resolve_columns, InputValue};
+use calamine::{Data, Ods, Range, Reader, Xlsx};
+use csv::StringRecord;
+use std::io::{self, BufRead, Cursor, Read, Seek};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpreadsheetFormat {
+    Xlsx,
+    Ods,
+}
+
+fn to_io_error(e: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+// reference of a cell such as B12, from its row and column indexes starting from 0
+fn cell_reference(row: u32, column: u32) -> String {
+    let mut letters: Vec<char> = Vec::new();
+    let mut column: u32 = column + 1;
+    while column > 0 {
+        letters.push((b'A' + ((column - 1) % 26) as u8) as char);
+        column = (column - 1) / 26;
+    }
+    letters.iter().rev().collect::<String>() + &(row + 1).to_string()
+}
+
+// reads the selected sheets of a XLSX or ODS workbook, all the sheets if none is selected,
+// the first row of each sheet being skipped as a header if it has one
+pub fn read_spreadsheet<F: FnMut(InputValue)>(
+    mut reader: impl BufRead,
+    format: SpreadsheetFormat,
+    sheets: &[String],
+    columns: &[String],
+    header: bool,
+    mut callback: F,
+) -> Result<(), io::Error> {
+    // workbooks are zip archives, which can't be read as a stream
+    let mut input: Vec<u8> = Vec::new();
+    reader.read_to_end(&mut input)?;
+    let input = Cursor::new(input);
+    let ranges: Vec<(String, Range<Data>)> = match format {
+        SpreadsheetFormat::Xlsx => read_sheets(Xlsx::new(input).map_err(to_io_error)?, sheets)?,
+        SpreadsheetFormat::Ods => read_sheets(Ods::new(input).map_err(to_io_error)?, sheets)?,
+    };
+    for (sheet, range) in ranges {
+        read_sheet(&sheet, &range, columns, header, &mut callback)?;
+    }
+    Ok(())
+}
+
+fn read_sheets<RS: Read + Seek, R: Reader<RS>>(
+    mut workbook: R,
+    sheets: &[String],
+) -> Result<Vec<(String, Range<Data>)>, io::Error>
+where
+    R::Error: ToString,
+{
+    let names: Vec<String> = workbook.sheet_names();
+    let selected: Vec<String> = if sheets.is_empty() {
+        names
+    } else {
+        let mut selected: Vec<String> = Vec::new();
+        for sheet in sheets {
+            let name: Option<&String> = names
+                .iter()
+                .find(|name| *name == sheet)
+                .or_else(|| names.get(sheet.parse::<usize>().ok()?));
+            match name {
+                Some(name) => selected.push(name.clone()),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("No sheet named {}", sheet),
+                    ))
+                }
+            }
+        }
+        selected
+    };
+    selected
+        .into_iter()
+        .map(|name| {
+            let range: Range<Data> = workbook.worksheet_range(&name).map_err(to_io_error)?;
+            Ok((name, range))
+        })
+        .collect()
+}
+
+fn read_sheet<F: FnMut(InputValue)>(
+    sheet: &str,
+    range: &Range<Data>,
+    columns: &[String],
+    header: bool,
+    callback: &mut F,
+) -> Result<(), io::Error> {
+    // the range starts at the first used cell, its rows are padded so that the indexes of the
+    // columns start from the column A
+    let (first_row, first_column): (u32, u32) = range.start().unwrap_or_default();
+    let mut rows = range
+        .rows()
+        .map(|row| {
+            let mut cells: Vec<String> = vec![String::new(); first_column as usize];
+            cells.extend(row.iter().map(Data::to_string));
+            StringRecord::from(cells)
+        })
+        .enumerate();
+    let header: Option<StringRecord> = if header {
+        rows.next().map(|(_, row)| row)
+    } else {
+        None
+    };
+    let resolved: Vec<(usize, String)> = if columns.is_empty() {
+        vec![(0, String::new())]
+    } else {
+        resolve_columns(columns, header.as_ref())?
+    };
+
+    for (index, row) in rows {
+        for (column, name) in &resolved {
+            let value: &str = match row.get(*column).map(str::trim) {
+                Some(value) if !value.is_empty() => value,
+                _ => continue,
+            };
+            let mut context: Vec<(String, String)> = vec![
+                ("sheet".to_string(), sheet.to_string()),
+                (
+                    "cell".to_string(),
+                    cell_reference(first_row + index as u32, *column as u32),
+                ),
+            ];
+            if !columns.is_empty() {
+                context.push(("column".to_string(), name.clone()));
+            }
+            callback(InputValue::with_context(refang(value), context));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+fn build_xlsx(sheets: &[(&str, &str)]) -> Vec<u8> {
+    use std::io::Write;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+
+    let mut workbook: String = String::from(
+        r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets>"#,
+    );
+    let mut relationships: String = String::from(
+        r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+    );
+    let mut files: Vec<(String, String)> = Vec::new();
+    for (index, (name, rows)) in sheets.iter().enumerate() {
+        workbook.push_str(&format!(
+            r#"<sheet name="{}" sheetId="{}" r:id="rId{}"/>"#,
+            name,
+            index + 1,
+            index + 1
+        ));
+        relationships.push_str(&format!(
+            r#"<Relationship Id="rId{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet{}.xml"/>"#,
+            index + 1,
+            index + 1
+        ));
+        files.push((
+            format!("xl/worksheets/sheet{}.xml", index + 1),
+            rows.to_string(),
+        ));
+    }
+    workbook.push_str("</sheets></workbook>");
+    relationships.push_str("</Relationships>");
+    files.push(("xl/workbook.xml".to_string(), workbook));
+    files.push(("xl/_rels/workbook.xml.rels".to_string(), relationships));
+    files.push((
+        "[Content_Types].xml".to_string(),
+        r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"/>"#
+            .to_string(),
+    ));
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (path, content) in files {
+        zip.start_file(path, SimpleFileOptions::default()).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+#[cfg(test)]
+fn sheet_xml(rows: &[&[(&str, &str)]]) -> String {
+    let mut xml: String = String::from(
+        r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>"#,
+    );
+    for (index, cells) in rows.iter().enumerate() {
+        xml.push_str(&format!(r#"<row r="{}">"#, index + 1));
+        for (reference, value) in cells.iter() {
+            xml.push_str(&format!(
+                r#"<c r="{}" t="inlineStr"><is><t>{}</t></is></c>"#,
+                reference, value
+            ));
+        }
+        xml.push_str("</row>");
+    }
+    xml.push_str("</sheetData></worksheet>");
+    xml
+}
+
+#[test]
+fn test_cell_reference() {
+    assert_eq!(cell_reference(0, 0), "A1");
+    assert_eq!(cell_reference(11, 1), "B12");
+    assert_eq!(cell_reference(0, 25), "Z1");
+    assert_eq!(cell_reference(0, 26), "AA1");
+    assert_eq!(cell_reference(4, 701), "ZZ5");
+}
+
+#[test]
+fn test_read_spreadsheet() {
+    let iocs: String = sheet_xml(&[
+        &[("A1", "comment"), ("B1", "indicator")],
+        &[("A2", "c2"), ("B2", "evil[.]com")],
+        &[("A3", "scanner"), ("B3", "10.0.0.1")],
+    ]);
+    let hashes: String = sheet_xml(&[&[("A1", "44d88612fea8a8f36de82e1278abb02f")]]);
+    let xlsx: Vec<u8> = build_xlsx(&[("IOCs", &iocs), ("Hashes", &hashes)]);
+    let read_with_header = |sheets: &[&str], columns: &[&str], header: bool| {
+        let columns: Vec<String> = columns.iter().map(|c| c.to_string()).collect();
+        let mut values: Vec<(String, String)> = Vec::new();
+        read_spreadsheet(
+            &xlsx[..],
+            SpreadsheetFormat::Xlsx,
+            &sheets
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<String>>(),
+            &columns,
+            crate::has_header(&columns, header),
+            |value| values.push((value.value.clone(), value.context_string())),
+        )
+        .map(|_| values)
+    };
+    let read = |sheets: &[&str], columns: &[&str]| read_with_header(sheets, columns, false);
+
+    assert_eq!(
+        read(&["IOCs"], &["indicator"]).unwrap(),
+        vec![
+            (
+                "evil.com".to_string(),
+                "sheet=IOCs;cell=B2;column=indicator".to_string()
+            ),
+            (
+                "10.0.0.1".to_string(),
+                "sheet=IOCs;cell=B3;column=indicator".to_string()
+            ),
+        ]
+    );
+    assert_eq!(
+        read(&["1"], &[]).unwrap(),
+        vec![(
+            "44d88612fea8a8f36de82e1278abb02f".to_string(),
+            "sheet=Hashes;cell=A1".to_string()
+        )]
+    );
+    let value = |value: &str, context: &str| (value.to_string(), context.to_string());
+    // the first row is only skipped as a header when asked to
+    assert_eq!(
+        read(&[], &[]).unwrap(),
+        vec![
+            value("comment", "sheet=IOCs;cell=A1"),
+            value("c2", "sheet=IOCs;cell=A2"),
+            value("scanner", "sheet=IOCs;cell=A3"),
+            value("44d88612fea8a8f36de82e1278abb02f", "sheet=Hashes;cell=A1"),
+        ]
+    );
+    assert_eq!(
+        read_with_header(&["IOCs"], &["1"], true).unwrap(),
+        vec![
+            value("evil.com", "sheet=IOCs;cell=B2;column=indicator"),
+            value("10.0.0.1", "sheet=IOCs;cell=B3;column=indicator"),
+        ]
+    );
+    assert!(read(&["Missing"], &[]).is_err());
+
+    // the column A is empty
+    let shifted: String = sheet_xml(&[
+        &[("B1", "comment"), ("C1", "indicator")],
+        &[("B2", "c2"), ("C2", "evil.com")],
+    ]);
+    let xlsx: Vec<u8> = build_xlsx(&[("Shifted", &shifted)]);
+    let mut values: Vec<(String, String)> = Vec::new();
+    read_spreadsheet(
+        &xlsx[..],
+        SpreadsheetFormat::Xlsx,
+        &[],
+        &["1".to_string()],
+        false,
+        |input_value| values.push((input_value.value.clone(), input_value.context_string())),
+    )
+    .unwrap();
+    assert_eq!(
+        values,
+        vec![
+            value("comment", "sheet=Shifted;cell=B1;column=1"),
+            value("c2", "sheet=Shifted;cell=B2;column=1"),
+        ]
+    );
+    let mut values: Vec<(String, String)> = Vec::new();
+    read_spreadsheet(
+        &xlsx[..],
+        SpreadsheetFormat::Xlsx,
+        &[],
+        &[],
+        false,
+        |input_value| values.push((input_value.value.clone(), input_value.context_string())),
+    )
+    .unwrap();
+    assert!(values.is_empty());
+    assert!(read_spreadsheet(
+        "not a workbook".as_bytes(),
+        SpreadsheetFormat::Ods,
+        &[],
+        &[],
+        false,
+        |_| ()
+    )
+    .is_err());
+}
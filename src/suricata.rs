@@ -0,0 +1,149 @@
+use crate::InputValue;
+use log::warn;
+use serde_json::Value;
+use std::io::{self, BufRead};
+
+// JSON pointers to the fields holding indicators in EVE events
+const INDICATOR_FIELDS: [(&str, &str); 11] = [
+    ("src_ip", "/src_ip"),
+    ("dest_ip", "/dest_ip"),
+    ("dns.rrname", "/dns/rrname"),
+    ("dns.queries.rrname", "/dns/queries"),
+    ("http.hostname", "/http/hostname"),
+    ("http.url", "/http/url"),
+    ("tls.sni", "/tls/sni"),
+    ("tls.fingerprint", "/tls/fingerprint"),
+    ("fileinfo.md5", "/fileinfo/md5"),
+    ("fileinfo.sha1", "/fileinfo/sha1"),
+    ("fileinfo.sha256", "/fileinfo/sha256"),
+];
+
+// reads the events of a Suricata eve.json file, one JSON object per line, skipping the
+// malformed lines such as the last one of a file being written
+pub fn read_eve_log<F: FnMut(InputValue)>(
+    mut reader: impl BufRead,
+    mut callback: F,
+) -> Result<(), io::Error> {
+    let mut line: String = String::new();
+    let mut line_number: usize = 0;
+    let mut nb_events: usize = 0;
+    let mut last_error: Option<String> = None;
+    while reader.read_line(&mut line)? > 0 {
+        line_number += 1;
+        if !line.trim().is_empty() {
+            match serde_json::from_str::<Value>(&line) {
+                Ok(event) => {
+                    nb_events += 1;
+                    read_event(&event, &mut callback);
+                }
+                Err(e) => {
+                    warn!("line {}: {}, the line is skipped", line_number, e);
+                    last_error = Some(format!("line {}: {}", line_number, e));
+                }
+            }
+        }
+        line.clear();
+    }
+    match last_error {
+        Some(e) if nb_events == 0 => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        _ => Ok(()),
+    }
+}
+
+fn read_event<F: FnMut(InputValue)>(event: &Value, callback: &mut F) {
+    let mut context: Vec<(String, String)> = Vec::new();
+    for key in ["flow_id", "timestamp", "event_type"] {
+        match event.get(key) {
+            Some(Value::String(value)) => context.push((key.to_string(), value.clone())),
+            Some(Value::Number(value)) => context.push((key.to_string(), value.to_string())),
+            _ => (),
+        }
+    }
+    for (field, pointer) in INDICATOR_FIELDS {
+        let values: Vec<String> = match (field, event.pointer(pointer)) {
+            (_, None) => continue,
+            // the queries of DNS events in the version 3 format
+            ("dns.queries.rrname", Some(Value::Array(queries))) => queries
+                .iter()
+                .filter_map(|query| query.get("rrname")?.as_str())
+                .map(str::to_string)
+                .collect(),
+            // the URL of HTTP events is usually only its path
+            ("http.url", Some(Value::String(url))) if url.starts_with('/') => {
+                match event.pointer("/http/hostname").and_then(Value::as_str) {
+                    Some(hostname) => vec![format!("http://{}{}", hostname, url)],
+                    None => continue,
+                }
+            }
+            // the SHA-1 fingerprint of the certificate, written as 3d:7c:...
+            ("tls.fingerprint", Some(Value::String(fingerprint))) => {
+                vec![fingerprint.replace(':', "")]
+            }
+            (_, Some(Value::String(value))) => vec![value.clone()],
+            _ => continue,
+        };
+        for value in values {
+            let value: &str = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            let mut context: Vec<(String, String)> = context.clone();
+            context.push(("field".to_string(), field.to_string()));
+            callback(InputValue::with_context(value.to_string(), context));
+        }
+    }
+}
+
+#[test]
+fn test_read_eve_log() {
+    let eve: &str = r#"{"timestamp":"2024-05-02T10:04:16.123456+0000","flow_id":1234567890123456,"event_type":"http","src_ip":"10.0.0.9","src_port":49822,"dest_ip":"1.2.3.4","dest_port":80,"proto":"TCP","http":{"hostname":"evil.com","url":"/a.php?b=c","http_method":"GET"}}
+
+{"timestamp":"2024-05-02T10:04:17.000000+0000","flow_id":42,"event_type":"dns","dns":{"version":3,"type":"request","queries":[{"rrname":"cdn.evil.com","rrtype":"A"}]}}
+{"timestamp":"2024-05-02T10:04:18.000000+0000","event_type":"tls","tls":{"sni":"evil.org","fingerprint":"3D:7C:00:11:22:33:44:55:66:77:88:99:aa:bb:cc:dd:ee:ff:00:11"}}
+"#;
+    let mut values: Vec<(String, String)> = Vec::new();
+    read_eve_log(eve.as_bytes(), |value| {
+        values.push((value.value.clone(), value.context_string()))
+    })
+    .unwrap();
+    let http_context = |field: &str| {
+        format!(
+            "flow_id=1234567890123456;timestamp=2024-05-02T10:04:16.123456+0000;event_type=http;field={}",
+            field
+        )
+    };
+    assert_eq!(
+        values,
+        vec![
+            ("10.0.0.9".to_string(), http_context("src_ip")),
+            ("1.2.3.4".to_string(), http_context("dest_ip")),
+            ("evil.com".to_string(), http_context("http.hostname")),
+            (
+                "http://evil.com/a.php?b=c".to_string(),
+                http_context("http.url")
+            ),
+            (
+                "cdn.evil.com".to_string(),
+                "flow_id=42;timestamp=2024-05-02T10:04:17.000000+0000;event_type=dns;field=dns.queries.rrname".to_string()
+            ),
+            (
+                "evil.org".to_string(),
+                "timestamp=2024-05-02T10:04:18.000000+0000;event_type=tls;field=tls.sni".to_string()
+            ),
+            (
+                "3D7C00112233445566778899aabbccddeeff0011".to_string(),
+                "timestamp=2024-05-02T10:04:18.000000+0000;event_type=tls;field=tls.fingerprint".to_string()
+            ),
+        ]
+    );
+
+    assert!(read_eve_log("{\"event_type\":".as_bytes(), |_| ()).is_err());
+    // the truncated last line of a file being written
+    let mut values: Vec<String> = Vec::new();
+    read_eve_log(
+        "{\"src_ip\":\"10.0.0.9\"}\n{\"event_type\":".as_bytes(),
+        |value| values.push(value.value),
+    )
+    .unwrap();
+    assert_eq!(values, vec!["10.0.0.9"]);
+}
@@ -0,0 +1,83 @@
+use crate::canonical::canonicalize;
+use crate::public_suffix::registered_domain;
+use std::net::IpAddr;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UrlComponents {
+    pub url: String,
+    pub host: Option<String>,
+    pub registered_domain: Option<String>,
+    pub ip: Option<IpAddr>,
+}
+
+pub fn decompose_url(url: &str) -> UrlComponents {
+    let url: String = canonicalize(url);
+    let mut components = UrlComponents {
+        url: url.clone(),
+        ..Default::default()
+    };
+    let rest: &str = match url.split_once("://") {
+        Some((_, rest)) => rest,
+        None => return components,
+    };
+    let authority: &str = &rest[..rest.find(['/', '?', '#']).unwrap_or(rest.len())];
+    let host_port: &str = match authority.rsplit_once('@') {
+        Some((_, host_port)) => host_port,
+        None => authority,
+    };
+    let host: &str = if let Some(bracketed) = host_port.strip_prefix('[') {
+        &bracketed[..bracketed.find(']').unwrap_or(bracketed.len())]
+    } else {
+        match host_port.rsplit_once(':') {
+            Some((host, _port)) => host,
+            None => host_port,
+        }
+    };
+    if host.is_empty() {
+        return components;
+    }
+    match host.parse::<IpAddr>() {
+        Ok(ip) => components.ip = Some(ip),
+        Err(_) => {
+            components.host = Some(host.to_string());
+            components.registered_domain =
+                registered_domain(host).filter(|registered| registered != host);
+        }
+    }
+    components
+}
+
+#[test]
+fn test_decompose_url() {
+    assert_eq!(
+        decompose_url("HTTPS://user@CDN.Evil.co.uk:8443/a.php?b=c"),
+        UrlComponents {
+            url: "https://user@cdn.evil.co.uk:8443/a.php?b=c".to_string(),
+            host: Some("cdn.evil.co.uk".to_string()),
+            registered_domain: Some("evil.co.uk".to_string()),
+            ip: None,
+        }
+    );
+    assert_eq!(
+        decompose_url("http://evil.com"),
+        UrlComponents {
+            url: "http://evil.com".to_string(),
+            host: Some("evil.com".to_string()),
+            registered_domain: None,
+            ip: None,
+        }
+    );
+    assert_eq!(
+        decompose_url("http://[2001:DB8::1]:80/"),
+        UrlComponents {
+            url: "http://[2001:db8::1]:80/".to_string(),
+            host: None,
+            registered_domain: None,
+            ip: Some("2001:db8::1".parse().unwrap()),
+        }
+    );
+    assert_eq!(
+        decompose_url("http://10.0.0.1/x").ip,
+        Some([10, 0, 0, 1].into())
+    );
+}
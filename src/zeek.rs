@@ -0,0 +1,286 @@
+use crate::InputValue;
+use log::warn;
+use serde_json::{Map, Value};
+use std::io::{self, BufRead};
+
+// fields holding indicators in conn, dns, http, ssl and files logs
+const INDICATOR_FIELDS: [&str; 10] = [
+    "id.orig_h",
+    "id.resp_h",
+    "tx_hosts",
+    "rx_hosts",
+    "query",
+    "host",
+    "server_name",
+    "md5",
+    "sha1",
+    "sha256",
+];
+
+// separators and fields declared by the header of a TSV log
+struct ZeekHeader {
+    separator: String,
+    set_separator: String,
+    unset_field: String,
+    empty_field: String,
+    fields: Vec<String>,
+    types: Vec<String>,
+}
+
+impl Default for ZeekHeader {
+    fn default() -> Self {
+        ZeekHeader {
+            separator: "\t".to_string(),
+            set_separator: ",".to_string(),
+            unset_field: "-".to_string(),
+            empty_field: "(empty)".to_string(),
+            fields: Vec::new(),
+            types: Vec::new(),
+        }
+    }
+}
+
+impl ZeekHeader {
+    fn parse_directive(&mut self, line: &str) {
+        let (directive, value) = match line.split_once(['\t', ' ']) {
+            Some((directive, value)) => (directive, value),
+            None => return,
+        };
+        match directive {
+            // the separator itself is escaped, e.g. `#separator \x09`
+            "#separator" => self.separator = unescape(value),
+            "#set_separator" => self.set_separator = value.to_string(),
+            "#unset_field" => self.unset_field = value.to_string(),
+            "#empty_field" => self.empty_field = value.to_string(),
+            "#fields" => self.fields = split_fields(value, &self.separator),
+            "#types" => self.types = split_fields(value, &self.separator),
+            _ => (),
+        }
+    }
+
+    fn field<'a>(&self, record: &[&'a str], name: &str) -> Option<&'a str> {
+        let index: usize = self.fields.iter().position(|field| field == name)?;
+        record
+            .get(index)
+            .copied()
+            .filter(|value| *value != self.unset_field && *value != self.empty_field)
+    }
+
+    fn is_container(&self, name: &str) -> bool {
+        self.fields
+            .iter()
+            .position(|field| field == name)
+            .and_then(|index| self.types.get(index))
+            .is_some_and(|field_type| {
+                field_type.starts_with("set[") || field_type.starts_with("vector[")
+            })
+    }
+}
+
+fn split_fields(value: &str, separator: &str) -> Vec<String> {
+    value.split(separator).map(str::to_string).collect()
+}
+
+fn unescape(value: &str) -> String {
+    match value
+        .strip_prefix("\\x")
+        .map(|hex| u8::from_str_radix(hex, 16))
+    {
+        Some(Ok(byte)) => (byte as char).to_string(),
+        _ => value.to_string(),
+    }
+}
+
+// reads Zeek logs in the TSV format, with its `#fields` header, or in the JSON format, one
+// object per line
+pub fn read_zeek_log<F: FnMut(InputValue)>(
+    mut reader: impl BufRead,
+    mut callback: F,
+) -> Result<(), io::Error> {
+    let mut header = ZeekHeader::default();
+    let mut line: String = String::new();
+    let mut line_number: usize = 0;
+    let mut nb_records: usize = 0;
+    let mut last_error: Option<String> = None;
+    while reader.read_line(&mut line)? > 0 {
+        line_number += 1;
+        let record: &str = line.trim_end_matches(['\n', '\r']);
+        if record.starts_with('{') {
+            // malformed records, such as the last one of a log being written, are skipped
+            match serde_json::from_str::<Map<String, Value>>(record) {
+                Ok(record) => {
+                    nb_records += 1;
+                    read_json_record(&record, &mut callback);
+                }
+                Err(e) => {
+                    warn!("line {}: {}, the line is skipped", line_number, e);
+                    last_error = Some(format!("line {}: {}", line_number, e));
+                }
+            }
+        } else if record.starts_with('#') {
+            header.parse_directive(record);
+        } else if !record.is_empty() {
+            read_tsv_record(record, line_number, &header, &mut callback)?;
+            nb_records += 1;
+        }
+        line.clear();
+    }
+    match last_error {
+        Some(e) if nb_records == 0 => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        _ => Ok(()),
+    }
+}
+
+fn read_tsv_record<F: FnMut(InputValue)>(
+    record: &str,
+    line_number: usize,
+    header: &ZeekHeader,
+    callback: &mut F,
+) -> Result<(), io::Error> {
+    if header.fields.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "line {}: Zeek log record before its #fields header",
+                line_number
+            ),
+        ));
+    }
+    let record: Vec<&str> = record.split(header.separator.as_str()).collect();
+    let context: Vec<(String, String)> = session_context(
+        header
+            .field(&record, "uid")
+            .or_else(|| header.field(&record, "fuid")),
+        header.field(&record, "ts"),
+    );
+    for name in INDICATOR_FIELDS {
+        let value: &str = match header.field(&record, name) {
+            Some(value) => value,
+            None => continue,
+        };
+        let values: Vec<&str> = if header.is_container(name) {
+            value.split(header.set_separator.as_str()).collect()
+        } else {
+            vec![value]
+        };
+        emit_values(values, name, &context, callback);
+    }
+    Ok(())
+}
+
+fn read_json_record<F: FnMut(InputValue)>(record: &Map<String, Value>, callback: &mut F) {
+    let ts: Option<String> = record.get("ts").map(|ts| match ts {
+        Value::String(ts) => ts.clone(),
+        ts => ts.to_string(),
+    });
+    let context: Vec<(String, String)> = session_context(
+        record
+            .get("uid")
+            .or_else(|| record.get("fuid"))
+            .and_then(Value::as_str),
+        ts.as_deref(),
+    );
+    for name in INDICATOR_FIELDS {
+        let values: Vec<&str> = match record.get(name) {
+            Some(Value::String(value)) => vec![value.as_str()],
+            Some(Value::Array(values)) => values.iter().filter_map(Value::as_str).collect(),
+            _ => continue,
+        };
+        emit_values(values, name, &context, callback);
+    }
+}
+
+fn session_context(uid: Option<&str>, ts: Option<&str>) -> Vec<(String, String)> {
+    let mut context: Vec<(String, String)> = Vec::new();
+    if let Some(uid) = uid {
+        context.push(("uid".to_string(), uid.to_string()));
+    }
+    if let Some(ts) = ts {
+        context.push(("ts".to_string(), ts.to_string()));
+    }
+    context
+}
+
+fn emit_values<F: FnMut(InputValue)>(
+    values: Vec<&str>,
+    field: &str,
+    context: &[(String, String)],
+    callback: &mut F,
+) {
+    for value in values.into_iter().map(str::trim) {
+        if value.is_empty() {
+            continue;
+        }
+        let mut context: Vec<(String, String)> = context.to_vec();
+        context.push(("field".to_string(), field.to_string()));
+        callback(InputValue::with_context(value.to_string(), context));
+    }
+}
+
+#[test]
+fn test_read_zeek_log() {
+    let read = |log: &str| {
+        let mut values: Vec<(String, String)> = Vec::new();
+        read_zeek_log(log.as_bytes(), |value| {
+            values.push((value.value.clone(), value.context_string()))
+        })
+        .unwrap();
+        values
+    };
+    let tsv: &str = "#separator \\x09\n\
+        #set_separator\t,\n\
+        #unset_field\t-\n\
+        #empty_field\t(empty)\n\
+        #path\tdns\n\
+        #fields\tts\tuid\tid.orig_h\tid.orig_p\tid.resp_h\tquery\tanswers\n\
+        #types\ttime\tstring\taddr\tport\taddr\tstring\tvector[string]\n\
+        1591367999.305988\tCMdzit1AMNsmfAIiQc\t192.168.4.76\t36844\t192.168.4.1\tevil.com\t1.2.3.4\n\
+        1591367999.306059\tC0LAHyvtKSQHyJxIl\t192.168.4.76\t36845\t-\t(empty)\t-\n\
+        #close\t2020-06-05-14-40-30\n";
+    assert_eq!(
+        read(tsv),
+        vec![
+            (
+                "192.168.4.76".to_string(),
+                "uid=CMdzit1AMNsmfAIiQc;ts=1591367999.305988;field=id.orig_h".to_string()
+            ),
+            (
+                "192.168.4.1".to_string(),
+                "uid=CMdzit1AMNsmfAIiQc;ts=1591367999.305988;field=id.resp_h".to_string()
+            ),
+            (
+                "evil.com".to_string(),
+                "uid=CMdzit1AMNsmfAIiQc;ts=1591367999.305988;field=query".to_string()
+            ),
+            (
+                "192.168.4.76".to_string(),
+                "uid=C0LAHyvtKSQHyJxIl;ts=1591367999.306059;field=id.orig_h".to_string()
+            ),
+        ]
+    );
+
+    let json: &str = "{\"ts\":1591367999.512593,\"fuid\":\"FMnxxt3xjVcWNS2141\",\"tx_hosts\":[\"1.2.3.4\"],\"rx_hosts\":[\"192.168.4.76\"],\"md5\":\"d41d8cd98f00b204e9800998ecf8427e\"}\n";
+    assert_eq!(
+        read(json),
+        vec![
+            (
+                "1.2.3.4".to_string(),
+                "uid=FMnxxt3xjVcWNS2141;ts=1591367999.512593;field=tx_hosts".to_string()
+            ),
+            (
+                "192.168.4.76".to_string(),
+                "uid=FMnxxt3xjVcWNS2141;ts=1591367999.512593;field=rx_hosts".to_string()
+            ),
+            (
+                "d41d8cd98f00b204e9800998ecf8427e".to_string(),
+                "uid=FMnxxt3xjVcWNS2141;ts=1591367999.512593;field=md5".to_string()
+            ),
+        ]
+    );
+
+    assert!(read_zeek_log("1591367999.305988\tC1\n".as_bytes(), |_| ()).is_err());
+    // the truncated last line of a log being written
+    let truncated: String = format!("{}{{\"ts\":1591367999.6,\"md5\":", json);
+    assert_eq!(read(&truncated).len(), 3);
+    assert!(read_zeek_log("{\"ts\":".as_bytes(), |_| ()).is_err());
+}
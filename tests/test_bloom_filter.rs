@@ -1,5 +1,12 @@
 use bloomfilter::Bloom;
-use dtl_hunter::{check_val_in_bloom, serialize_bloom};
+use dtl_hunter::atom_type::AtomType;
+use dtl_hunter::ip_ranges::split_ip_ranges;
+use dtl_hunter::{
+    add_url_components, check_val_in_bloom, check_values_in_bloom_file, classify_values,
+    create_bloom, create_bloom_with_seed, deserialize_bloom_file, seed_from_passphrase,
+    serialize_bloom, verify_bloom, write_bloom_to_file, BloomChecker, BloomFile, BloomMetadata,
+    CheckOptions, MatchedComponent, SECONDS_PER_DAY,
+};
 #[path = "common.rs"]
 mod common;
 
@@ -41,3 +48,168 @@ fn test_check_val_in_bloom() {
     let res = check_val_in_bloom(bloom, &values);
     assert_eq!(res, expected)
 }
+
+#[test]
+fn test_check_values_in_bloom_file() {
+    let source: Vec<String> = vec!["evil.com".to_string(), "10.0.0.1".to_string()];
+    let mut bloom_file = BloomFile::new(create_bloom(source, 10, 0.0001), None);
+    bloom_file.metadata.atom_types = Some(vec![AtomType::Ipv4, AtomType::Domain]);
+    let input = classify_values(vec![
+        "cdn.login.EVIL.com".to_string(),
+        "10.0.0.1".to_string(),
+        "other.com".to_string(),
+    ]);
+
+    let matches = check_values_in_bloom_file(&bloom_file, &input, &CheckOptions::default());
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].input.value, "10.0.0.1");
+
+    let options = CheckOptions {
+        parent_domains: true,
+        ..Default::default()
+    };
+    let matches = check_values_in_bloom_file(&bloom_file, &input, &options);
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0].input.value, "cdn.login.EVIL.com");
+    assert_eq!(matches[0].input.atom_type, AtomType::Fqdn);
+    assert_eq!(matches[0].matched_value, "evil.com");
+    assert_eq!(matches[0].matched_component, MatchedComponent::ParentDomain);
+}
+
+#[test]
+fn test_bloom_checker_skipped_types() {
+    let source: Vec<String> = vec!["evil.com".to_string(), "10.0.0.1".to_string()];
+    let mut bloom_file = BloomFile::new(create_bloom(source, 10, 0.0001), None);
+    bloom_file.metadata.atom_types = Some(vec![AtomType::Ipv4, AtomType::Fqdn]);
+    let input = classify_values(vec![
+        "evil.com".to_string(),
+        "other.com".to_string(),
+        "10.0.0.1".to_string(),
+    ]);
+    let mut checker = BloomChecker::new(&bloom_file);
+    let matches: Vec<String> = input
+        .iter()
+        .filter_map(|value| checker.check(value, &CheckOptions::default()))
+        .map(|bloom_match| bloom_match.matched_value)
+        .collect();
+    assert_eq!(matches, vec!["10.0.0.1"]);
+    // the domains are not checked, evil.com being in the bloom filter
+    assert_eq!(checker.nb_skipped(), 2);
+    assert_eq!(checker.nb_skipped_matches(), 1);
+}
+
+#[test]
+fn test_check_url_components_in_bloom_file() {
+    let source: Vec<String> = vec!["evil.com".to_string(), "10.0.0.1".to_string()];
+    let bloom_file = BloomFile::new(create_bloom(source, 10, 0.0001), None);
+    let input = classify_values(vec![
+        "https://cdn.evil.com/a.php".to_string(),
+        "http://10.0.0.1:8080/".to_string(),
+        "https://other.com/".to_string(),
+    ]);
+    let options = CheckOptions {
+        url_components: true,
+        ..Default::default()
+    };
+    let matches = check_values_in_bloom_file(&bloom_file, &input, &options);
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0].matched_value, "evil.com");
+    assert_eq!(
+        matches[0].matched_component,
+        MatchedComponent::RegisteredDomain
+    );
+    assert_eq!(matches[1].matched_value, "10.0.0.1");
+    assert_eq!(matches[1].matched_component, MatchedComponent::Ip);
+
+    // the registered domain of a URL is not added, as it may be a shared hosting service
+    let values = add_url_components(vec![
+        "https://sites.google.com/view/evil".to_string(),
+        "http://10.0.0.1:8080/".to_string(),
+        "sites.google.com".to_string(),
+    ]);
+    assert_eq!(
+        values,
+        vec![
+            "https://sites.google.com/view/evil",
+            "sites.google.com",
+            "http://10.0.0.1:8080/",
+            "10.0.0.1"
+        ]
+    );
+}
+
+#[test]
+fn test_check_ip_ranges_in_bloom_file() {
+    let (values, ranges) = split_ip_ranges(vec![
+        "10.0.0.0/8".to_string(),
+        "10.1.0.0/16".to_string(),
+        "evil.com".to_string(),
+    ]);
+    assert_eq!(values, vec!["evil.com".to_string()]);
+    let mut bloom_file = BloomFile::new(create_bloom(values, 10, 0.0001), None);
+    bloom_file.ranges = ranges;
+    let input = classify_values(vec![
+        "10.1.2.3".to_string(),
+        "10.2.0.1".to_string(),
+        "11.0.0.1".to_string(),
+    ]);
+    let matches = check_values_in_bloom_file(&bloom_file, &input, &CheckOptions::default());
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0].matched_value, "10.1.0.0/16");
+    assert_eq!(matches[0].matched_component, MatchedComponent::Range);
+    assert_eq!(matches[1].matched_value, "10.0.0.0/8");
+}
+
+#[test]
+fn test_verify_bloom() {
+    let source = vec![
+        "test1".to_string(),
+        "test2".to_string(),
+        "test3".to_string(),
+    ];
+    // a seeded bloom filter, so that test4 is never a false positive
+    let seed: [u8; 32] = seed_from_passphrase("test");
+    let bloom = create_bloom_with_seed(source.clone(), 5, 0.01, Some(&seed));
+    let report = verify_bloom(&bloom, &source, 1000);
+    assert_eq!(report.nb_checked, 3);
+    assert!(report.missing.is_empty());
+    assert_eq!(report.nb_samples, 1000);
+
+    let source = vec!["test1".to_string(), "test4".to_string()];
+    let report = verify_bloom(&bloom, &source, 0);
+    assert_eq!(report.missing, vec!["test4".to_string()]);
+    assert_eq!(report.observed_rate(), 0.0);
+}
+
+#[test]
+fn test_deserialize_bloom_file() {
+    let bloom = common::create_test_bloom();
+    let legacy_path = std::env::temp_dir().join("dtl_hunter_test_legacy.bloom");
+    std::fs::write(&legacy_path, serialize_bloom(&bloom).unwrap()).unwrap();
+    let legacy = deserialize_bloom_file(&legacy_path).unwrap();
+    assert_eq!(legacy.metadata, BloomMetadata::default());
+    assert!(legacy.bloom.check(&"test1".to_string()));
+
+    let path = std::env::temp_dir().join("dtl_hunter_test_metadata.bloom");
+    let mut bloom_file = BloomFile::new(bloom, Some(7));
+    bloom_file.metadata.rate = Some(0.001);
+    write_bloom_to_file(&bloom_file, &path).unwrap();
+    let bloom_file = deserialize_bloom_file(&path).unwrap();
+    let created_at = bloom_file.metadata.created_at.unwrap();
+    assert_eq!(bloom_file.metadata.rate, Some(0.001));
+    assert_eq!(
+        bloom_file.metadata.expires_at,
+        Some(created_at + 7 * SECONDS_PER_DAY)
+    );
+    assert_eq!(
+        bloom_file
+            .metadata
+            .age_in_days(created_at + SECONDS_PER_DAY),
+        Some(1)
+    );
+    assert!(!bloom_file.metadata.is_expired(created_at));
+    assert!(bloom_file
+        .metadata
+        .is_expired(created_at + 7 * SECONDS_PER_DAY));
+    assert!(bloom_file.bloom.check(&"test1".to_string()));
+}
@@ -0,0 +1,94 @@
+use dtl_hunter::bundle::{export_bundle, import_bundle};
+use dtl_hunter::{deserialize_bloom_file, write_bloom_to_file, BloomFile};
+use std::path::PathBuf;
+#[path = "common.rs"]
+mod common;
+
+fn create_test_bundle(name: &str, key: Option<&[u8]>) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let bloom_path = dir.join("test.bloom");
+    write_bloom_to_file(
+        &BloomFile::new(common::create_test_bloom(), None),
+        &bloom_path,
+    )
+    .unwrap();
+    let bundle_path = dir.join("bundle.tar.gz");
+    let manifest = export_bundle(&[bloom_path], &bundle_path, key).unwrap();
+    assert_eq!(manifest.filters.len(), 1);
+    bundle_path
+}
+
+#[test]
+fn test_export_import_bundle() {
+    let bundle_path = create_test_bundle("dtl_hunter_test_bundle", Some(b"secret"));
+    let output_dir = std::env::temp_dir().join("dtl_hunter_test_bundle_import");
+    let _ = std::fs::remove_dir_all(&output_dir);
+    let manifest = import_bundle(&bundle_path, &output_dir, Some(b"secret"), false).unwrap();
+    assert_eq!(manifest.filters[0].name, "test.bloom");
+    let bloom_file = deserialize_bloom_file(&output_dir.join("test.bloom")).unwrap();
+    assert!(bloom_file.bloom.check(&"test1".to_string()));
+
+    // installed bloom filters are only replaced with force
+    assert!(import_bundle(&bundle_path, &output_dir, Some(b"secret"), false).is_err());
+    assert!(import_bundle(&bundle_path, &output_dir, Some(b"secret"), true).is_ok());
+    let names: Vec<String> = std::fs::read_dir(&output_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(names, vec!["test.bloom"]);
+}
+
+#[test]
+fn test_import_bundle_rejected() {
+    let output_dir = std::env::temp_dir().join("dtl_hunter_test_bundle_rejected");
+    let bundle_path = create_test_bundle("dtl_hunter_test_bundle_signed", Some(b"secret"));
+    assert!(import_bundle(&bundle_path, &output_dir, Some(b"wrong"), false).is_err());
+    let bundle_path = create_test_bundle("dtl_hunter_test_bundle_unsigned", None);
+    assert!(import_bundle(&bundle_path, &output_dir, Some(b"secret"), false).is_err());
+    assert!(!output_dir.exists());
+}
+
+#[test]
+fn test_import_bundle_corrupted() {
+    use dtl_hunter::bundle::{BundleEntry, BundleManifest, MANIFEST_FILENAME};
+    use sha2::{Digest, Sha256};
+
+    // a bloom filter matching its checksum which is not a bloom filter
+    let content: &[u8] = b"not a bloom filter";
+    let manifest = BundleManifest {
+        created_at: 0,
+        filters: vec![BundleEntry {
+            name: "corrupted.bloom".to_string(),
+            checksum: dtl_hunter::to_hex(&Sha256::digest(content)),
+            metadata: Default::default(),
+            signature: None,
+        }],
+    };
+    let dir = std::env::temp_dir().join("dtl_hunter_test_bundle_corrupted");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let bundle_path = dir.join("bundle.tar.gz");
+    let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(
+        std::fs::File::create(&bundle_path).unwrap(),
+        flate2::Compression::default(),
+    ));
+    let manifest: String = ron::to_string(&manifest).unwrap();
+    for (name, content) in [
+        (MANIFEST_FILENAME, manifest.as_bytes()),
+        ("corrupted.bloom", content),
+    ] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        archive.append_data(&mut header, name, content).unwrap();
+    }
+    archive.into_inner().unwrap().finish().unwrap();
+
+    let output_dir = dir.join("filters");
+    let e = import_bundle(&bundle_path, &output_dir, None, false).unwrap_err();
+    assert!(e.contains("can't be deserialized"), "{}", e);
+    assert_eq!(std::fs::read_dir(&output_dir).unwrap().count(), 0);
+}
@@ -0,0 +1,58 @@
+use dtl_hunter::{resolve_bloom_paths, write_bloom_to_file, BloomFile, CATALOG_FILENAME};
+use std::path::PathBuf;
+#[path = "common.rs"]
+mod common;
+
+fn create_test_dir(name: &str, catalog: Option<&str>) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for filename in ["c2.bloom", "phishing.bloom", "old.bloom"] {
+        let bloom_file = BloomFile::new(common::create_test_bloom(), None);
+        write_bloom_to_file(&bloom_file, &dir.join(filename)).unwrap();
+    }
+    std::fs::write(dir.join("notes.txt"), "not a bloom filter").unwrap();
+    if let Some(catalog) = catalog {
+        std::fs::write(dir.join(CATALOG_FILENAME), catalog).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_resolve_bloom_directory() {
+    let dir = create_test_dir("dtl_hunter_test_bloom_dir", None);
+    let resolved = resolve_bloom_paths(&vec![dir.clone()], &[]).unwrap();
+    let names: Vec<String> = resolved.into_iter().map(|(name, _)| name).collect();
+    assert_eq!(names, vec!["c2.bloom", "old.bloom", "phishing.bloom"]);
+    assert!(resolve_bloom_paths(&vec![dir.clone()], &["c2".to_string()]).is_err());
+    assert!(resolve_bloom_paths(&vec![dir.join("c2.bloom")], &["c2".to_string()]).is_err());
+
+    // bloom filters with the same name in two directories
+    let other_dir = create_test_dir("dtl_hunter_test_bloom_dir_other", None);
+    assert!(resolve_bloom_paths(&vec![dir, other_dir], &[]).is_err());
+}
+
+#[test]
+fn test_resolve_bloom_catalog() {
+    let catalog = r#"(
+    filters: [
+        (path: "c2.bloom", label: Some("C2 servers"), tags: ["c2", "network"]),
+        (path: "phishing.bloom", tags: ["phishing", "network"]),
+        (path: "old.bloom", enabled: false, tags: ["c2"]),
+    ],
+)"#;
+    let dir = create_test_dir("dtl_hunter_test_bloom_catalog", Some(catalog));
+    let resolved = resolve_bloom_paths(&vec![dir.clone()], &[]).unwrap();
+    assert_eq!(
+        resolved,
+        vec![
+            ("C2 servers".to_string(), dir.join("c2.bloom")),
+            ("phishing.bloom".to_string(), dir.join("phishing.bloom")),
+        ]
+    );
+    let resolved = resolve_bloom_paths(&vec![dir.clone()], &["c2".to_string()]).unwrap();
+    assert_eq!(
+        resolved,
+        vec![("C2 servers".to_string(), dir.join("c2.bloom"))]
+    );
+}
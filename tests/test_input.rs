@@ -0,0 +1,133 @@
+use dtl_hunter::{
+    for_each_input_value, read_input, read_input_file, read_input_values, InputOptions,
+};
+use std::path::PathBuf;
+
+fn create_test_file(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+fn test_read_first_column() {
+    let path = create_test_file(
+        "dtl_hunter_test_first_column.csv",
+        "# comment\nevil.com,1\n\n10.0.0.1,2\n",
+    );
+    let options = InputOptions {
+        comment: Some(b'#'),
+        ..Default::default()
+    };
+    let values = read_input(&path, &options).unwrap();
+    assert_eq!(values, vec!["evil.com", "10.0.0.1"]);
+    // no line is a comment by default
+    let values = read_input(&path, &InputOptions::default()).unwrap();
+    assert_eq!(values, vec!["# comment", "evil.com", "10.0.0.1"]);
+}
+
+#[test]
+fn test_read_header() {
+    // the first row is kept unless a header is asked for
+    let path = create_test_file("dtl_hunter_test_header.csv", "test1\nevil.com\n");
+    let values = read_input(&path, &InputOptions::default()).unwrap();
+    assert_eq!(values, vec!["test1", "evil.com"]);
+    let options = InputOptions {
+        header: true,
+        ..Default::default()
+    };
+    let values = read_input(&path, &options).unwrap();
+    assert_eq!(values, vec!["evil.com"]);
+
+    let path = create_test_file("dtl_hunter_test_header_empty.csv", "");
+    assert!(read_input(&path, &options).unwrap().is_empty());
+    let options = InputOptions {
+        columns: vec!["domain".to_string()],
+        ..Default::default()
+    };
+    assert!(read_input(&path, &options).is_err());
+}
+
+#[test]
+fn test_read_columns() {
+    let path = create_test_file(
+        "dtl_hunter_test_columns.csv",
+        "src_ip\tdst_ip\tdomain\n10.0.0.1\t1.2.3.4\tevil.com\n10.0.0.2\t\tbad.com\n",
+    );
+    let options = InputOptions {
+        columns: vec!["domain".to_string(), "1".to_string()],
+        delimiter: b'\t',
+        ..Default::default()
+    };
+    let values = read_input_values(&path, &options).unwrap();
+    let values: Vec<(String, String)> = values
+        .into_iter()
+        .map(|value| (value.value.clone(), value.context_string()))
+        .collect();
+    assert_eq!(
+        values,
+        vec![
+            ("evil.com".to_string(), "column=domain".to_string()),
+            ("1.2.3.4".to_string(), "column=dst_ip".to_string()),
+            ("bad.com".to_string(), "column=domain".to_string()),
+        ]
+    );
+
+    let options = InputOptions {
+        columns: vec!["missing".to_string()],
+        delimiter: b'\t',
+        ..Default::default()
+    };
+    assert!(read_input_values(&path, &options).is_err());
+}
+
+#[test]
+fn test_read_without_header() {
+    let path = create_test_file(
+        "dtl_hunter_test_without_header.csv",
+        "evil.com|10.0.0.1\nbad.com|10.0.0.2\n",
+    );
+    let options = InputOptions {
+        columns: vec!["1".to_string()],
+        delimiter: b'|',
+        ..Default::default()
+    };
+    let values = read_input_values(&path, &options).unwrap();
+    assert_eq!(values.len(), 2);
+    assert_eq!(values[0].value, "10.0.0.1");
+    assert_eq!(values[0].context_string(), "column=1");
+}
+
+#[test]
+fn test_read_input_stream() {
+    let mut values: Vec<String> = Vec::new();
+    read_input_file(
+        "evil.com\n10.0.0.1\n".as_bytes(),
+        &InputOptions::default(),
+        |input_value| values.push(input_value.value),
+    )
+    .unwrap();
+    assert_eq!(values, vec!["evil.com", "10.0.0.1"]);
+}
+
+#[test]
+fn test_read_undecodable_bytes() {
+    // the quoted field spanning two lines stays closed, and its value is dropped
+    let path = std::env::temp_dir().join("dtl_hunter_test_undecodable.csv");
+    std::fs::write(
+        &path,
+        b"domain,comment\nevil.com,\"caf\xe9\nbar\"\nbad.com,\n",
+    )
+    .unwrap();
+    let options = InputOptions {
+        columns: vec!["domain".to_string(), "comment".to_string()],
+        ..Default::default()
+    };
+    let mut values: Vec<String> = Vec::new();
+    let replaced_lines = for_each_input_value(&path, &options, |input_value| {
+        values.push(input_value.value)
+    })
+    .unwrap();
+    assert_eq!(values, vec!["evil.com", "bad.com"]);
+    assert_eq!(replaced_lines, vec![2]);
+}
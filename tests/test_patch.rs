@@ -0,0 +1,43 @@
+use dtl_hunter::patch::{apply_patch, diff_blooms};
+use dtl_hunter::{bloom_checksum, create_bloom_with_seed, seed_from_passphrase, BloomFile};
+#[path = "common.rs"]
+mod common;
+
+#[test]
+fn test_diff_and_apply_patch() {
+    let seed = seed_from_passphrase("test");
+    let old_values = vec!["test1".to_string(), "test2".to_string()];
+    let new_values = vec![
+        "test1".to_string(),
+        "test2".to_string(),
+        "test3".to_string(),
+    ];
+    let old = BloomFile::new(
+        create_bloom_with_seed(old_values, 10, 0.01, Some(&seed)),
+        None,
+    );
+    let new = BloomFile::new(
+        create_bloom_with_seed(new_values, 10, 0.01, Some(&seed)),
+        None,
+    );
+
+    let patch = diff_blooms(&old, &new).unwrap();
+    assert!(patch.nb_changed_bits() > 0);
+    let patched = apply_patch(&old, &patch).unwrap();
+    assert_eq!(bloom_checksum(&patched.bloom), bloom_checksum(&new.bloom));
+    assert!(patched.bloom.check(&"test3".to_string()));
+    // the patch can only be applied on the bloom filter it was created from
+    assert!(apply_patch(&patched, &patch).is_err());
+
+    // gaps overflowing the position of the bits
+    let mut invalid_patch = patch.clone();
+    invalid_patch.changed_bits = vec![1, u64::MAX];
+    assert!(apply_patch(&old, &invalid_patch).is_err());
+}
+
+#[test]
+fn test_diff_different_parameters() {
+    let old = BloomFile::new(common::create_test_bloom(), None);
+    let new = BloomFile::new(common::create_test_bloom(), None);
+    assert!(diff_blooms(&old, &new).is_err());
+}